@@ -113,10 +113,6 @@
                 after = [ "network-online.target" ];
                 wants = [ "network-online.target" ];
                 startLimitIntervalSec = 0;
-                path = with pkgs; [
-                  curl
-                  zstd
-                ];
                 environment = {
                   LD_LIBRARY_PATH = "${pkgs.stdenv.cc.cc.lib}/lib/";
                 };
@@ -0,0 +1,197 @@
+//! Compares reading `User` records stored with MessagePack against rkyv archives.
+//!
+//! Records are copied from the `users` column family of a server DB when
+//! `RKYVDB_BENCH_USERS` points to one, and generated otherwise.
+
+use std::collections::BTreeMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rkyvdb::{ArchivedCollection, CaseInsensitiveString, Collection, Database};
+
+const ROOT: &str = "magnus";
+const MAX_USERS: usize = 100_000;
+
+/// Mirror of the server's `User`, with `time` kept in its serialized form.
+#[derive(
+    serde::Serialize, serde::Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+#[archive(check_bytes)]
+struct User {
+    id: String,
+    erdos_links: BTreeMap<String, Vec<ErdosLink>>,
+}
+
+#[derive(
+    serde::Serialize, serde::Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+#[archive(check_bytes)]
+struct ErdosLink {
+    erdos_number: u32,
+    loser_id: String,
+    time: String,
+    winner_info: PlayerInfo,
+    loser_info: PlayerInfo,
+    game_id: String,
+    move_count: u32,
+    time_control: TimeControl,
+    winner_is_white: bool,
+    termination: Termination,
+}
+
+#[derive(
+    serde::Serialize, serde::Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+#[archive(check_bytes)]
+struct PlayerInfo {
+    title: String,
+    rating: u32,
+    rating_change: i32,
+}
+
+#[derive(
+    serde::Serialize, serde::Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+#[archive(check_bytes)]
+struct TimeControl {
+    game_type: TimeControlType,
+    main: u32,
+    increment: u32,
+}
+
+#[derive(
+    serde::Serialize, serde::Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+#[archive(check_bytes)]
+enum TimeControlType {
+    Blitz,
+    Rapid,
+    Classical,
+}
+
+#[derive(
+    serde::Serialize, serde::Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize,
+)]
+#[archive(check_bytes)]
+enum Termination {
+    Checkmate,
+    Resign,
+    Time,
+}
+
+impl Collection for User {
+    type KeyType = CaseInsensitiveString;
+    const CF_NAME: &'static str = "users_rmp";
+}
+
+impl ArchivedCollection for User {
+    type KeyType = CaseInsensitiveString;
+    const CF_NAME: &'static str = "users_rkyv";
+}
+
+fn read_users(path: &str) -> Vec<User> {
+    let opts = rocksdb::Options::default();
+    let cfs = rocksdb::DB::list_cf(&opts, path).unwrap();
+    let db = rocksdb::DB::open_cf_for_read_only(&opts, path, cfs, false).unwrap();
+    let cf = db.cf_handle("users").expect("Not a server DB");
+    db.iterator_cf(cf, rocksdb::IteratorMode::Start)
+        .filter_map(|entry| rmp_serde::decode::from_slice(&entry.unwrap().1).ok())
+        .take(MAX_USERS)
+        .collect()
+}
+
+fn generate_users() -> Vec<User> {
+    let player_info = |rating| PlayerInfo {
+        title: String::new(),
+        rating,
+        rating_change: -5,
+    };
+    (0..MAX_USERS)
+        .map(|index| User {
+            id: format!("Player{index}"),
+            erdos_links: BTreeMap::from([(
+                ROOT.to_string(),
+                (0..index % 6)
+                    .map(|link| ErdosLink {
+                        erdos_number: (10 - link) as u32,
+                        loser_id: format!("Opponent{link}"),
+                        time: "2021-03-04T05:06:07Z".to_string(),
+                        winner_info: player_info(1500),
+                        loser_info: player_info(1600),
+                        game_id: "AbCdEfGh".to_string(),
+                        move_count: 42,
+                        time_control: TimeControl {
+                            game_type: TimeControlType::Blitz,
+                            main: 180,
+                            increment: 2,
+                        },
+                        winner_is_white: true,
+                        termination: Termination::Resign,
+                    })
+                    .collect(),
+            )]),
+        })
+        .collect()
+}
+
+fn formats(c: &mut Criterion) {
+    let users = match std::env::var("RKYVDB_BENCH_USERS") {
+        Ok(path) => read_users(&path),
+        Err(_) => generate_users(),
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::build()
+        .add_collection::<User>()
+        .add_archived_collection::<User>()
+        .open(dir.path().to_str().unwrap())
+        .unwrap();
+    for user in &users {
+        Collection::put(&user.id, user, &db).unwrap();
+        ArchivedCollection::put(&user.id, user, &db).unwrap();
+    }
+    let ids: Vec<&str> = users.iter().map(|user| user.id.as_str()).collect();
+
+    let mut group = c.benchmark_group("latest_erdos_number");
+    group.bench_function("rmp", |b| {
+        let mut ids = ids.iter().cycle();
+        b.iter(|| {
+            let user = <User as Collection>::get(*ids.next().unwrap(), &db)
+                .unwrap()
+                .unwrap();
+            black_box(
+                user.erdos_links
+                    .get(ROOT)
+                    .and_then(|erdos_links| erdos_links.last())
+                    .map(|erdos_link| erdos_link.erdos_number),
+            )
+        })
+    });
+    group.bench_function("rkyv", |b| {
+        let mut ids = ids.iter().cycle();
+        b.iter(|| {
+            let user = User::get_archived(*ids.next().unwrap(), &db)
+                .unwrap()
+                .unwrap();
+            black_box(
+                user.erdos_links
+                    .get(ROOT)
+                    .and_then(|erdos_links| erdos_links.last())
+                    .map(|erdos_link| erdos_link.erdos_number),
+            )
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("get_owned");
+    group.bench_function("rmp", |b| {
+        let mut ids = ids.iter().cycle();
+        b.iter(|| black_box(<User as Collection>::get(*ids.next().unwrap(), &db).unwrap()))
+    });
+    group.bench_function("rkyv", |b| {
+        let mut ids = ids.iter().cycle();
+        b.iter(|| black_box(<User as ArchivedCollection>::get(*ids.next().unwrap(), &db).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, formats);
+criterion_main!(benches);
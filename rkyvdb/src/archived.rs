@@ -0,0 +1,143 @@
+use std::{marker::PhantomData, ops::Deref};
+
+use rkyv::{
+    bytecheck::CheckBytes, ser::serializers::AllocSerializer,
+    validation::validators::DefaultValidator, AlignedVec, Archive, Deserialize, Infallible,
+};
+
+use crate::{backend::Value, Database, Error, OwnedKey, Store, Tuning};
+
+enum Bytes<'a> {
+    Pinned(Value<'a>),
+    /// Copy of a value which the backend returned at an address unsuitable for rkyv.
+    Aligned(AlignedVec),
+}
+
+impl<'a> Bytes<'a> {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Bytes::Pinned(slice) => &**slice,
+            Bytes::Aligned(vec) => &**vec,
+        }
+    }
+}
+
+/// Validated archived value, borrowed from the backend's cache when it's aligned.
+pub struct ArchivedRef<'a, T> {
+    bytes: Bytes<'a>,
+    collection: PhantomData<T>,
+}
+
+impl<'a, T> ArchivedRef<'a, T> {
+    /// Whether the value is read in place, rather than from a copy made for alignment.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.bytes, Bytes::Pinned(_))
+    }
+}
+
+impl<'a, T: Archive> Deref for ArchivedRef<'a, T> {
+    type Target = T::Archived;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: bytes were validated with `check_archived_root` in `get_archived` and aren't
+        // mutated since.
+        unsafe { rkyv::archived_root::<T>(self.bytes.as_slice()) }
+    }
+}
+
+fn decode_archived<T: ArchivedCollection>(value: &[u8]) -> Result<T, Error>
+where
+    T::Archived: for<'b> CheckBytes<DefaultValidator<'b>> + Deserialize<T, Infallible>,
+{
+    let mut aligned = AlignedVec::with_capacity(value.len());
+    aligned.extend_from_slice(value);
+    let archived = rkyv::check_archived_root::<T>(&aligned).map_err(|_| Error::InvalidArchive)?;
+    Ok(archived.deserialize(&mut Infallible).unwrap())
+}
+
+fn encode_archived<T: ArchivedCollection>(value: &T) -> Result<Vec<u8>, Error> {
+    rkyv::to_bytes::<_, 256>(value)
+        .map(AlignedVec::into_vec)
+        .map_err(|_| Error::RkyvEncode)
+}
+
+/// Like `Collection`, but stores values as rkyv archives so that `get_archived` can read them
+/// in place instead of deserializing every field.
+///
+/// Archived collections have no schema version, migrations or indexes, and are left out of
+/// `Database::export` and `Database::import`: changing the archived type means rewriting the
+/// collection.
+pub trait ArchivedCollection: Archive + rkyv::Serialize<AllocSerializer<256>> + Sized {
+    type KeyType: OwnedKey;
+    const CF_NAME: &'static str;
+
+    /// See `Collection::TUNING`, applied by `DatabaseBuilder::add_archived_collection`.
+    const TUNING: Tuning = Tuning::DEFAULT;
+
+    fn get_archived<K: Into<Self::KeyType>>(
+        key: K,
+        db: &Database,
+    ) -> Result<Option<ArchivedRef<'_, Self>>, Error>
+    where
+        Self::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+    {
+        let key: Self::KeyType = key.into();
+        let value = match db.backend.get(Self::CF_NAME, &key.serialize())? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let bytes = if value.as_ptr() as usize % AlignedVec::ALIGNMENT == 0 {
+            Bytes::Pinned(value)
+        } else {
+            let mut aligned = AlignedVec::with_capacity(value.len());
+            aligned.extend_from_slice(&value);
+            Bytes::Aligned(aligned)
+        };
+        rkyv::check_archived_root::<Self>(bytes.as_slice()).map_err(|_| Error::InvalidArchive)?;
+        Ok(Some(ArchivedRef {
+            bytes,
+            collection: PhantomData,
+        }))
+    }
+
+    fn get<K: Into<Self::KeyType>>(key: K, db: &impl Store) -> Result<Option<Self>, Error>
+    where
+        Self::Archived: for<'b> CheckBytes<DefaultValidator<'b>> + Deserialize<Self, Infallible>,
+    {
+        let key: Self::KeyType = key.into();
+        db.read(Self::CF_NAME, &key.serialize(), |value| {
+            value.map(decode_archived::<Self>).transpose()
+        })
+    }
+
+    fn put<K: Into<Self::KeyType>>(key: K, value: &Self, db: &impl Store) -> Result<(), Error> {
+        let key: Self::KeyType = key.into();
+        db.write(
+            Self::CF_NAME,
+            &key.serialize(),
+            Some(encode_archived(value)?),
+        )
+    }
+
+    fn delete<K: Into<Self::KeyType>>(key: K, db: &impl Store) -> Result<(), Error> {
+        let key: Self::KeyType = key.into();
+        db.write(Self::CF_NAME, &key.serialize(), None)
+    }
+
+    fn modify<K: Into<Self::KeyType>>(
+        key: K,
+        db: &impl Store,
+        modifier: impl FnOnce(Option<Self>) -> Option<Self>,
+    ) -> Result<(), Error>
+    where
+        Self::Archived: for<'b> CheckBytes<DefaultValidator<'b>> + Deserialize<Self, Infallible>,
+    {
+        let key: Self::KeyType = key.into();
+        db.update(Self::CF_NAME, &key.serialize(), |old_value| {
+            let old_value = old_value.map(decode_archived::<Self>).transpose()?;
+            modifier(old_value)
+                .map(|value| encode_archived(&value))
+                .transpose()
+        })
+    }
+}
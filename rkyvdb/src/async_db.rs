@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use tokio::{sync::Semaphore, task::spawn_blocking};
+
+use crate::{Collection, Database, Error};
+
+/// Handle to a `Database` for async code, which runs blocking calls on tokio's blocking pool.
+/// At most `max_blocking` calls run at once, later ones wait for a slot without blocking an
+/// executor thread.
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    db: Database,
+    permits: Arc<Semaphore>,
+}
+
+impl AsyncDatabase {
+    pub fn new(db: Database, max_blocking: usize) -> Self {
+        AsyncDatabase {
+            db,
+            permits: Arc::new(Semaphore::new(max_blocking)),
+        }
+    }
+
+    pub fn db(&self) -> &Database {
+        &self.db
+    }
+
+    /// Runs `f` on the blocking pool, e.g. to make several dependent reads in one go. Panics in
+    /// `f` are resumed in the caller.
+    pub async fn run<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&Database) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let permit = Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .expect("Semaphore is never closed");
+        let db = self.db.clone();
+        let task = spawn_blocking(move || {
+            let _permit = permit;
+            f(&db)
+        });
+        match task.await {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => panic!("Blocking DB call didn't run: {err}"),
+        }
+    }
+
+    pub async fn get<T>(&self, key: impl Into<T::KeyType>) -> Result<Option<T>, Error>
+    where
+        T: Collection + Send + 'static,
+        T::KeyType: Send + 'static,
+    {
+        let key = key.into();
+        self.run(move |db| T::get(key, db)).await
+    }
+
+    /// Reads records of all `keys` in one blocking call, in the order of `keys`.
+    pub async fn multi_get<T, K>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Result<Vec<Option<T>>, Error>
+    where
+        T: Collection + Send + 'static,
+        T::KeyType: Send + 'static,
+        K: Into<T::KeyType>,
+    {
+        let keys: Vec<T::KeyType> = keys.into_iter().map(Into::into).collect();
+        self.run(move |db| T::multi_get(keys, db)).await
+    }
+
+    pub async fn put<T>(&self, key: impl Into<T::KeyType>, value: T) -> Result<(), Error>
+    where
+        T: Collection + Send + 'static,
+        T::KeyType: Send + 'static,
+    {
+        let key = key.into();
+        self.run(move |db| T::put(key, &value, db)).await
+    }
+
+    pub async fn delete<T>(&self, key: impl Into<T::KeyType>) -> Result<(), Error>
+    where
+        T: Collection + Send + 'static,
+        T::KeyType: Send + 'static,
+    {
+        let key = key.into();
+        self.run(move |db| T::delete(key, db)).await
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::{Bound, Deref},
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use crate::{CollectionStats, Direction, Error, Write};
+
+/// Column family of bookkeeping like schema versions, which every backend has. It's RocksDB's
+/// default column family.
+pub(crate) const METADATA_CF_NAME: &str = "default";
+
+/// Value read from a backend, borrowed from its cache where possible.
+pub(crate) struct Value<'a>(Box<dyn AsRef<[u8]> + 'a>);
+
+impl<'a> Value<'a> {
+    fn new(bytes: impl AsRef<[u8]> + 'a) -> Self {
+        Value(Box::new(bytes))
+    }
+}
+
+impl<'a> Deref for Value<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        (*self.0).as_ref()
+    }
+}
+
+/// `(key, value)` pairs of a column family, see `Backend::scan`.
+pub(crate) type ScanIter<'a> = Box<dyn Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), Error>> + 'a>;
+
+/// Range of keys visited by `Backend::scan`.
+pub(crate) struct Scan<'a> {
+    /// Key to start at, or the nearest one after it in `direction`. Without it the scan starts
+    /// at the first or the last key.
+    pub from: Option<&'a [u8]>,
+    pub direction: Direction,
+    /// Only keys starting with it are visited.
+    pub prefix: &'a [u8],
+}
+
+impl<'a> Scan<'a> {
+    pub fn all(direction: Direction) -> Self {
+        Scan {
+            from: None,
+            direction,
+            prefix: &[],
+        }
+    }
+}
+
+/// Storage of the column families a `Database` consists of. Reads and writes of a missing
+/// column family fail with `Error::CollectionNotRegistered`.
+pub(crate) trait Backend: Send + Sync {
+    fn has_cf(&self, cf_name: &str) -> bool;
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Value<'_>>, Error>;
+
+    /// Values of `keys`, in the same order.
+    fn multi_get(&self, cf_name: &str, keys: &[&[u8]]) -> Result<Vec<Option<Value<'_>>>, Error>;
+
+    /// Applies all writes atomically.
+    fn write(&self, writes: Vec<Write>) -> Result<(), Error>;
+
+    /// Iterates over a snapshot of the column family taken when the scan starts.
+    fn scan(&self, cf_name: &str, scan: Scan<'_>) -> Result<ScanIter<'_>, Error>;
+
+    fn delete_prefix(&self, cf_name: &str, prefix: &[u8]) -> Result<(), Error>;
+
+    /// See `Database::checkpoint`.
+    fn checkpoint(&self, path: &Path) -> Result<(), Error>;
+
+    /// See `Database::catch_up_with_primary`.
+    fn catch_up(&self) -> Result<(), Error>;
+
+    fn stats(&self, cf_name: &str) -> Result<CollectionStats, Error>;
+
+    /// See `Database::compact`.
+    fn compact(&self, cf_name: &str) -> Result<(), Error>;
+}
+
+/// Smallest byte string greater than every string starting with `prefix`, if any.
+pub(crate) fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+pub(crate) struct RocksDbBackend {
+    db: rocksdb::DB,
+    /// False for DBs opened read-only or as a secondary instance, where RocksDB rejects writes
+    /// with a less helpful error.
+    writable: bool,
+    /// Lengths of the prefix extractors of column families, see `Tuning::prefix_len`.
+    prefix_lens: HashMap<&'static str, usize>,
+}
+
+impl RocksDbBackend {
+    pub fn new(db: rocksdb::DB) -> Self {
+        RocksDbBackend {
+            db,
+            writable: true,
+            prefix_lens: HashMap::new(),
+        }
+    }
+
+    pub fn read_only(db: rocksdb::DB) -> Self {
+        RocksDbBackend {
+            db,
+            writable: false,
+            prefix_lens: HashMap::new(),
+        }
+    }
+
+    pub fn with_prefix_lens(mut self, prefix_lens: HashMap<&'static str, usize>) -> Self {
+        self.prefix_lens = prefix_lens;
+        self
+    }
+
+    fn cf(&self, cf_name: &str) -> Result<&rocksdb::ColumnFamily, Error> {
+        self.db
+            .cf_handle(cf_name)
+            .ok_or(Error::CollectionNotRegistered)
+    }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.writable {
+            Ok(())
+        } else {
+            Err(Error::ReadOnly)
+        }
+    }
+}
+
+impl Backend for RocksDbBackend {
+    fn has_cf(&self, cf_name: &str) -> bool {
+        self.db.cf_handle(cf_name).is_some()
+    }
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Value<'_>>, Error> {
+        let cf = self.cf(cf_name)?;
+        Ok(self.db.get_pinned_cf(cf, key)?.map(Value::new))
+    }
+
+    fn multi_get(&self, cf_name: &str, keys: &[&[u8]]) -> Result<Vec<Option<Value<'_>>>, Error> {
+        let cf = self.cf(cf_name)?;
+        self.db
+            .multi_get_cf(keys.iter().map(|key| (cf, key)))
+            .into_iter()
+            .map(|value| Ok(value?.map(Value::new)))
+            .collect()
+    }
+
+    fn write(&self, writes: Vec<Write>) -> Result<(), Error> {
+        self.check_writable()?;
+        let mut batch = rocksdb::WriteBatch::default();
+        for write in writes {
+            let cf = self.cf(write.cf_name)?;
+            if let Some(value) = write.value {
+                batch.put_cf(cf, write.key, value);
+            } else {
+                batch.delete_cf(cf, write.key);
+            }
+        }
+        Ok(self.db.write(batch)?)
+    }
+
+    fn scan(&self, cf_name: &str, scan: Scan<'_>) -> Result<ScanIter<'_>, Error> {
+        let cf = self.cf(cf_name)?;
+        let mut opts = rocksdb::ReadOptions::default();
+        if !scan.prefix.is_empty() {
+            if let Some(end) = prefix_end(scan.prefix) {
+                opts.set_iterate_upper_bound(end);
+            }
+            opts.set_iterate_lower_bound(scan.prefix);
+        }
+        // RocksDB only seeks correctly within prefixes the extractor covers, otherwise it has to
+        // ignore its prefix filters.
+        if let Some(&prefix_len) = self.prefix_lens.get(cf_name) {
+            opts.set_total_order_seek(scan.prefix.len() < prefix_len);
+        }
+        let mode = match (scan.from, scan.direction) {
+            (Some(from), direction) => rocksdb::IteratorMode::From(from, direction),
+            (None, Direction::Forward) => rocksdb::IteratorMode::Start,
+            (None, Direction::Reverse) => rocksdb::IteratorMode::End,
+        };
+        Ok(Box::new(
+            self.db
+                .iterator_cf_opt(cf, opts, mode)
+                .map(|entry| entry.map_err(Error::RocksDB)),
+        ))
+    }
+
+    fn delete_prefix(&self, cf_name: &str, prefix: &[u8]) -> Result<(), Error> {
+        self.check_writable()?;
+        let cf = self.cf(cf_name)?;
+        if let Some(end) = prefix_end(prefix) {
+            return Ok(self.db.delete_range_cf(cf, prefix, &end)?);
+        }
+        let mut batch = rocksdb::WriteBatch::default();
+        let scan = Scan {
+            from: None,
+            direction: Direction::Forward,
+            prefix,
+        };
+        for entry in self.scan(cf_name, scan)? {
+            batch.delete_cf(cf, entry?.0);
+        }
+        Ok(self.db.write(batch)?)
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), Error> {
+        rocksdb::checkpoint::Checkpoint::new(&self.db)?.create_checkpoint(path)?;
+        Ok(())
+    }
+
+    fn catch_up(&self) -> Result<(), Error> {
+        Ok(self.db.try_catch_up_with_primary()?)
+    }
+
+    fn stats(&self, cf_name: &str) -> Result<CollectionStats, Error> {
+        use rocksdb::properties::{
+            PropName, ESTIMATE_NUM_KEYS, ESTIMATE_PENDING_COMPACTION_BYTES, TOTAL_SST_FILES_SIZE,
+        };
+        let cf = self.cf(cf_name)?;
+        let property = |name: &PropName| -> Result<u64, Error> {
+            Ok(self.db.property_int_value_cf(cf, name)?.unwrap_or(0))
+        };
+        Ok(CollectionStats {
+            estimated_keys: property(ESTIMATE_NUM_KEYS)?,
+            sst_size: property(TOTAL_SST_FILES_SIZE)?,
+            pending_compaction_bytes: property(ESTIMATE_PENDING_COMPACTION_BYTES)?,
+        })
+    }
+
+    fn compact(&self, cf_name: &str) -> Result<(), Error> {
+        self.check_writable()?;
+        let cf = self.cf(cf_name)?;
+        self.db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        Ok(())
+    }
+}
+
+type Table = BTreeMap<Vec<u8>, Arc<[u8]>>;
+
+/// Keeps column families in memory, so that tests don't touch the filesystem.
+pub(crate) struct MemoryBackend(RwLock<HashMap<&'static str, Table>>);
+
+impl MemoryBackend {
+    pub fn new(cf_names: impl IntoIterator<Item = &'static str>) -> Self {
+        let cfs = cf_names
+            .into_iter()
+            .chain([METADATA_CF_NAME])
+            .map(|cf_name| (cf_name, Table::new()))
+            .collect();
+        MemoryBackend(RwLock::new(cfs))
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn has_cf(&self, cf_name: &str) -> bool {
+        self.0.read().unwrap().contains_key(cf_name)
+    }
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Value<'_>>, Error> {
+        let cfs = self.0.read().unwrap();
+        let table = cfs.get(cf_name).ok_or(Error::CollectionNotRegistered)?;
+        Ok(table.get(key).cloned().map(Value::new))
+    }
+
+    fn multi_get(&self, cf_name: &str, keys: &[&[u8]]) -> Result<Vec<Option<Value<'_>>>, Error> {
+        let cfs = self.0.read().unwrap();
+        let table = cfs.get(cf_name).ok_or(Error::CollectionNotRegistered)?;
+        Ok(keys
+            .iter()
+            .map(|key| table.get(*key).cloned().map(Value::new))
+            .collect())
+    }
+
+    fn write(&self, writes: Vec<Write>) -> Result<(), Error> {
+        let mut cfs = self.0.write().unwrap();
+        if writes.iter().any(|write| !cfs.contains_key(write.cf_name)) {
+            return Err(Error::CollectionNotRegistered);
+        }
+        for write in writes {
+            let table = cfs.get_mut(write.cf_name).unwrap();
+            if let Some(value) = write.value {
+                table.insert(write.key, value.into());
+            } else {
+                table.remove(&write.key);
+            }
+        }
+        Ok(())
+    }
+
+    fn scan(&self, cf_name: &str, scan: Scan<'_>) -> Result<ScanIter<'_>, Error> {
+        let cfs = self.0.read().unwrap();
+        let table = cfs.get(cf_name).ok_or(Error::CollectionNotRegistered)?;
+        let prefix = scan.prefix;
+        let entry = |(key, value): (&Vec<u8>, &Arc<[u8]>)| -> (Box<[u8]>, Box<[u8]>) {
+            (Box::from(key.as_slice()), Box::from(&**value))
+        };
+        let entries: Vec<_> = match scan.direction {
+            Direction::Forward => {
+                let start = scan.from.filter(|from| *from > prefix).unwrap_or(prefix);
+                table
+                    .range::<[u8], _>((Bound::Included(start), Bound::Unbounded))
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .map(entry)
+                    .collect()
+            }
+            Direction::Reverse => {
+                let end = scan.from.map_or(Bound::Unbounded, Bound::Included);
+                table
+                    .range::<[u8], _>((Bound::Unbounded, end))
+                    .rev()
+                    .skip_while(|(key, _)| !key.starts_with(prefix) && key.as_slice() > prefix)
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .map(entry)
+                    .collect()
+            }
+        };
+        Ok(Box::new(entries.into_iter().map(Ok)))
+    }
+
+    fn delete_prefix(&self, cf_name: &str, prefix: &[u8]) -> Result<(), Error> {
+        let mut cfs = self.0.write().unwrap();
+        let table = cfs.get_mut(cf_name).ok_or(Error::CollectionNotRegistered)?;
+        table.retain(|key, _| !key.starts_with(prefix));
+        Ok(())
+    }
+
+    fn checkpoint(&self, _path: &Path) -> Result<(), Error> {
+        Err(Error::Unsupported("Checkpoints of in-memory DBs"))
+    }
+
+    fn catch_up(&self) -> Result<(), Error> {
+        Err(Error::Unsupported("Secondary instances of in-memory DBs"))
+    }
+
+    fn stats(&self, cf_name: &str) -> Result<CollectionStats, Error> {
+        let cfs = self.0.read().unwrap();
+        let table = cfs.get(cf_name).ok_or(Error::CollectionNotRegistered)?;
+        Ok(CollectionStats {
+            estimated_keys: table.len() as u64,
+            ..Default::default()
+        })
+    }
+
+    fn compact(&self, cf_name: &str) -> Result<(), Error> {
+        if self.has_cf(cf_name) {
+            Ok(())
+        } else {
+            Err(Error::CollectionNotRegistered)
+        }
+    }
+}
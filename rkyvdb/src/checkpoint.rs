@@ -0,0 +1,86 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{Database, Error};
+
+impl Database {
+    /// Writes a consistent copy of the DB to `path`, which must not exist yet. Files are
+    /// hard-linked when `path` is on the same filesystem, so this is cheap.
+    pub fn checkpoint(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.backend.checkpoint(path.as_ref())
+    }
+}
+
+/// Directory of named checkpoints, ordered by name.
+pub struct CheckpointDir {
+    path: PathBuf,
+}
+
+impl CheckpointDir {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CheckpointDir { path: path.into() }
+    }
+
+    /// Creates checkpoint `name`, replacing an existing one with the same name.
+    pub fn create(&self, db: &Database, name: &str) -> Result<PathBuf, Error> {
+        fs::create_dir_all(&self.path)?;
+        let path = self.path.join(name);
+        // Checkpoints are written next to the final path and renamed, so that a crash never
+        // leaves a partial checkpoint under a valid name.
+        let tmp = self.path.join(format!(".{name}.tmp"));
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp)?;
+        }
+        db.checkpoint(&tmp)?;
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    pub fn list(&self) -> Result<Vec<String>, Error> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        let mut names = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_dir() && !name.starts_with('.') {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Deletes all but the last `keep` checkpoints whose name starts with `prefix`, returning
+    /// the deleted names.
+    pub fn prune(&self, prefix: &str, keep: usize) -> Result<Vec<String>, Error> {
+        let mut names = self.list()?;
+        names.retain(|name| name.starts_with(prefix));
+        names.truncate(names.len().saturating_sub(keep));
+        for name in &names {
+            fs::remove_dir_all(self.path.join(name))?;
+        }
+        Ok(names)
+    }
+
+    /// Copies checkpoint `name` to `to`, which can then be opened as a regular DB while the
+    /// checkpoint stays untouched.
+    pub fn restore(&self, name: &str, to: &Path) -> Result<(), Error> {
+        let from = self.path.join(name);
+        if !from.is_dir() {
+            return Err(Error::CheckpointNotFound(name.to_string()));
+        }
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(&from)? {
+            let entry = entry?;
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+        Ok(())
+    }
+}
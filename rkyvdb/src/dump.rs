@@ -0,0 +1,297 @@
+use std::{
+    fmt,
+    io::{BufRead, Write},
+    marker::PhantomData,
+};
+
+use serde::{
+    de::{self, DeserializeOwned, IgnoredAny, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+use crate::{backend::Scan, Collection, Database, Direction, Error, OwnedKey};
+
+/// Records are committed in batches of this many while importing.
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One JSON object per line.
+    Json,
+    /// Consecutive MessagePack maps.
+    MessagePack,
+}
+
+/// Entry of a dump. Every collection starts with its name and schema version, followed by its
+/// records with their decoded `Collection::KeyType`, e.g. in JSON:
+///
+/// ```text
+/// {"collection":"users","schema_version":1}
+/// {"key":"alice","value":{...}}
+/// {"collection":"games","schema_version":0}
+/// {"key":["alice",1357000000],"value":{...}}
+/// ```
+///
+/// Composite keys are arrays of their fields, and `()` keys are `null`.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Entry<K, V> {
+    Record {
+        key: K,
+        value: V,
+    },
+    Collection {
+        collection: String,
+        schema_version: u32,
+    },
+}
+
+/// Decodes `value` in place rather than buffering it as derived untagged enums do, which
+/// loses e.g. integer map keys of JSON values.
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for Entry<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntryVisitor<K, V>(PhantomData<(K, V)>);
+
+        impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for EntryVisitor<K, V> {
+            type Value = Entry<K, V>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a collection header or a record")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let (mut key, mut value, mut collection, mut schema_version) =
+                    (None, None, None, None);
+                while let Some(field) = map.next_key::<String>()? {
+                    match field.as_str() {
+                        "key" => key = Some(map.next_value()?),
+                        "value" => value = Some(map.next_value()?),
+                        "collection" => collection = Some(map.next_value()?),
+                        "schema_version" => schema_version = Some(map.next_value()?),
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                match (key, value, collection, schema_version) {
+                    (Some(key), Some(value), None, None) => Ok(Entry::Record { key, value }),
+                    (None, None, Some(collection), Some(schema_version)) => Ok(Entry::Collection {
+                        collection,
+                        schema_version,
+                    }),
+                    _ => Err(de::Error::custom(
+                        "expected either `key` and `value` or `collection` and `schema_version`",
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_map(EntryVisitor(PhantomData))
+    }
+}
+
+struct DumpWriter<'a> {
+    out: &'a mut dyn Write,
+    format: DumpFormat,
+}
+
+impl<'a> DumpWriter<'a> {
+    fn write<K: Serialize, V: Serialize>(&mut self, entry: &Entry<K, V>) -> Result<(), Error> {
+        match self.format {
+            DumpFormat::Json => {
+                serde_json::to_writer(&mut *self.out, entry)?;
+                self.out.write_all(b"\n")?;
+            }
+            DumpFormat::MessagePack => {
+                // Named fields, so that entries can be told apart.
+                let mut serializer = rmp_serde::Serializer::new(&mut *self.out).with_struct_map();
+                entry.serialize(&mut serializer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct DumpReader<'a> {
+    input: &'a mut dyn BufRead,
+    format: DumpFormat,
+    line: String,
+}
+
+impl<'a> DumpReader<'a> {
+    /// Reads the next entry, or `None` at the end of the dump.
+    fn next<K: DeserializeOwned, V: DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<Entry<K, V>>, Error> {
+        match self.format {
+            DumpFormat::Json => loop {
+                self.line.clear();
+                if self.input.read_line(&mut self.line)? == 0 {
+                    return Ok(None);
+                }
+                if !self.line.trim().is_empty() {
+                    return Ok(Some(serde_json::from_str(&self.line)?));
+                }
+            },
+            DumpFormat::MessagePack => {
+                if self.input.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(rmp_serde::decode::from_read(&mut *self.input)?))
+            }
+        }
+    }
+}
+
+/// Header of a collection in a dump, read ahead of its records.
+struct Header {
+    collection: String,
+    schema_version: u32,
+}
+
+/// Export and import of one registered collection.
+pub(crate) struct Dumper {
+    cf_name: &'static str,
+    schema_version: u32,
+    export: fn(&Database, &mut DumpWriter) -> Result<u64, Error>,
+    import: fn(&Database, &mut DumpReader) -> Result<(u64, Option<Header>), Error>,
+}
+
+impl Dumper {
+    pub fn new<T: Collection>() -> Self
+    where
+        T::KeyType: Serialize + DeserializeOwned,
+    {
+        Dumper {
+            cf_name: T::CF_NAME,
+            schema_version: T::SCHEMA_VERSION,
+            export: export_collection::<T>,
+            import: import_collection::<T>,
+        }
+    }
+}
+
+fn export_collection<T: Collection>(db: &Database, out: &mut DumpWriter) -> Result<u64, Error>
+where
+    T::KeyType: Serialize,
+{
+    let mut records = 0;
+    for entry in db.backend.scan(T::CF_NAME, Scan::all(Direction::Forward))? {
+        let (key, value) = entry?;
+        let key = <T::KeyType as OwnedKey>::deserialize(&key)?;
+        let value: T = rmp_serde::decode::from_slice(&value)?;
+        out.write(&Entry::Record { key, value })?;
+        records += 1;
+    }
+    Ok(records)
+}
+
+/// Puts records of `T` until the header of the next collection, which is returned.
+fn import_collection<T: Collection>(
+    db: &Database,
+    input: &mut DumpReader,
+) -> Result<(u64, Option<Header>), Error>
+where
+    T::KeyType: DeserializeOwned,
+{
+    let mut batch = db.batch();
+    let mut records = 0;
+    let next = loop {
+        match input.next::<T::KeyType, T>()? {
+            Some(Entry::Record { key, value }) => {
+                T::put(key, &value, &batch)?;
+                records += 1;
+            }
+            Some(Entry::Collection {
+                collection,
+                schema_version,
+            }) => {
+                break Some(Header {
+                    collection,
+                    schema_version,
+                })
+            }
+            None => break None,
+        };
+        if batch.len() >= IMPORT_BATCH_SIZE {
+            batch.commit()?;
+        }
+    };
+    batch.commit()?;
+    Ok((records, next))
+}
+
+impl Database {
+    /// Streams all records of every collection added with `DatabaseBuilder::add_collection`
+    /// to `out`, returning the number of records per collection. Collections are read one
+    /// after another, so writes made meanwhile may be included in some of them only.
+    /// Archived collections and secondary indexes are not exported.
+    pub fn export(
+        &self,
+        mut out: impl Write,
+        format: DumpFormat,
+    ) -> Result<Vec<(&'static str, u64)>, Error> {
+        let mut out = DumpWriter {
+            out: &mut out,
+            format,
+        };
+        let mut counts = vec![];
+        for dumper in &self.dumpers {
+            out.write::<(), ()>(&Entry::Collection {
+                collection: dumper.cf_name.to_string(),
+                schema_version: dumper.schema_version,
+            })?;
+            counts.push((dumper.cf_name, (dumper.export)(self, &mut out)?));
+        }
+        out.out.flush()?;
+        Ok(counts)
+    }
+
+    /// Puts all records of a dump written by `export`, returning the number of records per
+    /// collection. Records overwrite existing ones with the same key, and secondary indexes
+    /// are updated as by `Collection::put`. Collections must be registered with the schema
+    /// version they were dumped with.
+    pub fn import(
+        &self,
+        mut input: impl BufRead,
+        format: DumpFormat,
+    ) -> Result<Vec<(&'static str, u64)>, Error> {
+        let mut input = DumpReader {
+            input: &mut input,
+            format,
+            line: String::new(),
+        };
+        let mut next = match input.next::<IgnoredAny, IgnoredAny>()? {
+            Some(Entry::Collection {
+                collection,
+                schema_version,
+            }) => Some(Header {
+                collection,
+                schema_version,
+            }),
+            Some(Entry::Record { .. }) => {
+                return Err(Error::InvalidDump("Record before the first collection"))
+            }
+            None => None,
+        };
+        let mut counts = vec![];
+        while let Some(header) = next {
+            let dumper = self
+                .dumpers
+                .iter()
+                .find(|dumper| dumper.cf_name == header.collection)
+                .ok_or(Error::CollectionNotRegistered)?;
+            if header.schema_version != dumper.schema_version {
+                return Err(Error::DumpSchemaMismatch {
+                    collection: dumper.cf_name,
+                    dumped: header.schema_version,
+                    supported: dumper.schema_version,
+                });
+            }
+            let (records, following) = (dumper.import)(self, &mut input)?;
+            counts.push((dumper.cf_name, records));
+            next = following;
+        }
+        Ok(counts)
+    }
+}
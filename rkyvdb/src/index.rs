@@ -0,0 +1,157 @@
+use std::marker::PhantomData;
+
+use crate::{
+    backend::{Backend, Scan, ScanIter, METADATA_CF_NAME},
+    Collection, Database, Direction, Error, OwnedKey, Store, Write,
+};
+
+/// Column family holding entries of every secondary index, keyed by
+/// `collection \0 index \0 value primary_key` with the primary key as the value.
+pub(crate) const INDEX_CF_NAME: &str = "rkyvdb_indexes";
+
+/// Entries written between saving progress when rebuilding an index.
+const REINDEX_BATCH_SIZE: usize = 10_000;
+
+fn signature_key(cf_name: &str) -> Vec<u8> {
+    format!("rkyvdb/indexes/{cf_name}").into_bytes()
+}
+
+/// Comma separated index names, to notice when declared indexes change.
+pub(crate) fn signature<T: Collection>() -> String {
+    T::INDEXES.join(",")
+}
+
+pub(crate) fn stored_signature(db: &dyn Backend, cf_name: &str) -> Result<String, Error> {
+    Ok(db
+        .get(METADATA_CF_NAME, &signature_key(cf_name))?
+        .map(|signature| String::from_utf8_lossy(&signature).into_owned())
+        .unwrap_or_default())
+}
+
+pub(crate) fn set_signature(db: &dyn Backend, cf_name: &str, signature: &str) -> Result<(), Error> {
+    db.write(vec![Write {
+        cf_name: METADATA_CF_NAME,
+        key: signature_key(cf_name),
+        value: Some(signature.as_bytes().to_vec()),
+    }])
+}
+
+fn collection_prefix(cf_name: &str) -> Vec<u8> {
+    [cf_name.as_bytes(), b"\0".as_slice()].concat()
+}
+
+fn entry_prefix(cf_name: &str, index: &str, value_prefix: &[u8]) -> Vec<u8> {
+    [
+        cf_name.as_bytes(),
+        b"\0".as_slice(),
+        index.as_bytes(),
+        b"\0".as_slice(),
+        value_prefix,
+    ]
+    .concat()
+}
+
+/// Values of `record` in each of `T::INDEXES`.
+pub(crate) fn index_values<T: Collection>(record: Option<&T>) -> Vec<Vec<Vec<u8>>> {
+    T::INDEXES
+        .iter()
+        .map(|index| record.map_or_else(Vec::new, |record| record.index_values(index)))
+        .collect()
+}
+
+/// Index entries to delete and add when a record changes from `old` to `new` values.
+pub(crate) fn index_writes<T: Collection>(
+    key: &[u8],
+    old: &[Vec<Vec<u8>>],
+    new: &[Vec<Vec<u8>>],
+) -> Vec<Write> {
+    let mut writes = vec![];
+    for ((index, old_values), new_values) in T::INDEXES.iter().zip(old).zip(new) {
+        for value in old_values {
+            if !new_values.contains(value) {
+                writes.push(Write {
+                    cf_name: INDEX_CF_NAME,
+                    key: [entry_prefix(T::CF_NAME, index, value).as_slice(), key].concat(),
+                    value: None,
+                });
+            }
+        }
+        for value in new_values {
+            if !old_values.contains(value) {
+                writes.push(Write {
+                    cf_name: INDEX_CF_NAME,
+                    key: [entry_prefix(T::CF_NAME, index, value).as_slice(), key].concat(),
+                    value: Some(key.to_vec()),
+                });
+            }
+        }
+    }
+    writes
+}
+
+/// Drops all index entries of `T` and recreates them from its records.
+pub(crate) fn reindex<T: Collection>(db: &Database) -> Result<(), Error> {
+    db.backend
+        .delete_prefix(INDEX_CF_NAME, &collection_prefix(T::CF_NAME))?;
+    let no_values = index_values::<T>(None);
+    let mut batch = vec![];
+    for entry in T::iter(db)? {
+        let (key, record) = entry?;
+        batch.extend(index_writes::<T>(
+            &key.serialize(),
+            &no_values,
+            &index_values(Some(&record)),
+        ));
+        if batch.len() >= REINDEX_BATCH_SIZE {
+            db.backend.write(std::mem::take(&mut batch))?;
+        }
+    }
+    db.backend.write(batch)
+}
+
+/// Records found through a secondary index, in order of their index values.
+pub struct IndexIter<'a, T> {
+    db: &'a Database,
+    inner: ScanIter<'a>,
+    collection: PhantomData<T>,
+}
+
+impl<'a, T: Collection> Iterator for IndexIter<'a, T> {
+    type Item = Result<(T::KeyType, T), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = match self.inner.next()? {
+            Ok((_, key)) => key,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(self.db.read(T::CF_NAME, &key, |value| {
+            let value = value.ok_or(Error::BrokenIndex)?;
+            Ok((
+                T::KeyType::deserialize(&key)?,
+                rmp_serde::decode::from_slice(value)?,
+            ))
+        }))
+    }
+}
+
+pub(crate) fn scan<'a, T: Collection>(
+    index: &'static str,
+    value_prefix: &[u8],
+    direction: Direction,
+    db: &'a Database,
+) -> Result<IndexIter<'a, T>, Error> {
+    if !T::INDEXES.contains(&index) {
+        return Err(Error::IndexNotDeclared(index));
+    }
+    let prefix = entry_prefix(T::CF_NAME, index, value_prefix);
+    let scan = Scan {
+        from: None,
+        direction,
+        prefix: &prefix,
+    };
+    Ok(IndexIter {
+        db,
+        inner: db.backend.scan(INDEX_CF_NAME, scan)?,
+        collection: PhantomData,
+    })
+}
@@ -0,0 +1,269 @@
+use std::{
+    borrow::Cow,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::Error;
+
+pub trait Key {
+    /// Bytes of the key in the DB, which orders keys bytewise by them.
+    fn serialize(&self) -> Cow<'_, [u8]>;
+}
+
+impl Key for () {
+    fn serialize(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&[])
+    }
+}
+
+impl Key for str {
+    fn serialize(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
+}
+
+impl Key for String {
+    fn serialize(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
+}
+
+/// Key which can be read back from the DB while iterating.
+pub trait OwnedKey: Key + Sized {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+impl OwnedKey for () {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidKey)
+        }
+    }
+}
+
+impl OwnedKey for String {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidKey)
+    }
+}
+
+pub struct CaseInsensitiveString(String);
+
+impl CaseInsensitiveString {
+    /// The key in lowercase.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for CaseInsensitiveString {
+    fn from(s: &str) -> Self {
+        Self(s.to_lowercase())
+    }
+}
+
+impl From<&String> for CaseInsensitiveString {
+    fn from(s: &String) -> Self {
+        Self(s.to_lowercase())
+    }
+}
+
+impl Key for CaseInsensitiveString {
+    fn serialize(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+}
+
+/// Serialized as the lowercase key, e.g. in dumps.
+impl serde::Serialize for CaseInsensitiveString {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for CaseInsensitiveString {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer).map(|key| Self::from(&key))
+    }
+}
+
+impl OwnedKey for CaseInsensitiveString {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        String::deserialize(bytes).map(Self)
+    }
+}
+
+/// Field of a composite key, i.e. a tuple of up to 4 fields. Fields are encoded so that tuples
+/// are ordered field by field, and a tuple of the first fields is a prefix of the full key, see
+/// `Collection::prefix_iter_by`.
+pub trait KeyPart: Sized {
+    fn write_part(&self, out: &mut Vec<u8>);
+
+    /// Reads the field from the start of `bytes` and advances past it.
+    fn read_part(bytes: &mut &[u8]) -> Result<Self, Error>;
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if bytes.len() < len {
+        return Err(Error::InvalidKey);
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+/// Integers are big-endian with the sign bit flipped, so that negative ones come first.
+macro_rules! integer_key {
+    ($($int:ty, $sign:expr;)*) => {$(
+        impl KeyPart for $int {
+            fn write_part(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&(self ^ $sign).to_be_bytes());
+            }
+
+            fn read_part(bytes: &mut &[u8]) -> Result<Self, Error> {
+                let bytes = take(bytes, std::mem::size_of::<$int>())?;
+                Ok(<$int>::from_be_bytes(bytes.try_into().unwrap()) ^ $sign)
+            }
+        }
+
+        impl Key for $int {
+            fn serialize(&self) -> Cow<'_, [u8]> {
+                let mut out = vec![];
+                self.write_part(&mut out);
+                Cow::Owned(out)
+            }
+        }
+
+        impl OwnedKey for $int {
+            fn deserialize(mut bytes: &[u8]) -> Result<Self, Error> {
+                let key = Self::read_part(&mut bytes)?;
+                bytes.is_empty().then_some(key).ok_or(Error::InvalidKey)
+            }
+        }
+    )*};
+}
+
+integer_key! {
+    u8, 0; u16, 0; u32, 0; u64, 0;
+    i8, i8::MIN; i16, i16::MIN; i32, i32::MIN; i64, i64::MIN;
+}
+
+/// Strings end with `\0 \x01`, and `\0` bytes in them are escaped as `\0 \xff`, so that a
+/// string sorts before any longer string it is a prefix of, and the end of a string can't be
+/// mistaken for an escaped `\0` whatever field follows it.
+fn write_escaped(string: &[u8], out: &mut Vec<u8>) {
+    for &byte in string {
+        out.push(byte);
+        if byte == 0 {
+            out.push(0xff);
+        }
+    }
+    out.extend_from_slice(&[0, 1]);
+}
+
+fn read_escaped(bytes: &mut &[u8]) -> Result<String, Error> {
+    let mut string = vec![];
+    loop {
+        match take(bytes, 1)?[0] {
+            0 => match take(bytes, 1)?[0] {
+                0xff => string.push(0),
+                1 => break,
+                _ => return Err(Error::InvalidKey),
+            },
+            byte => string.push(byte),
+        }
+    }
+    String::from_utf8(string).map_err(|_| Error::InvalidKey)
+}
+
+impl KeyPart for String {
+    fn write_part(&self, out: &mut Vec<u8>) {
+        write_escaped(self.as_bytes(), out);
+    }
+
+    fn read_part(bytes: &mut &[u8]) -> Result<Self, Error> {
+        read_escaped(bytes)
+    }
+}
+
+impl KeyPart for CaseInsensitiveString {
+    fn write_part(&self, out: &mut Vec<u8>) {
+        write_escaped(self.0.as_bytes(), out);
+    }
+
+    fn read_part(bytes: &mut &[u8]) -> Result<Self, Error> {
+        read_escaped(bytes).map(Self)
+    }
+}
+
+/// Seconds since the epoch as an `i64` followed by nanoseconds as a `u32`, so that times
+/// before the epoch work too.
+impl KeyPart for SystemTime {
+    fn write_part(&self, out: &mut Vec<u8>) {
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i64), 0),
+                    nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+                }
+            }
+        };
+        secs.write_part(out);
+        nanos.write_part(out);
+    }
+
+    fn read_part(bytes: &mut &[u8]) -> Result<Self, Error> {
+        let secs = i64::read_part(bytes)?;
+        let nanos = Duration::from_nanos(u32::read_part(bytes)?.into());
+        Ok(if secs >= 0 {
+            UNIX_EPOCH + Duration::from_secs(secs as u64) + nanos
+        } else {
+            UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + nanos
+        })
+    }
+}
+
+impl Key for SystemTime {
+    fn serialize(&self) -> Cow<'_, [u8]> {
+        let mut out = vec![];
+        self.write_part(&mut out);
+        Cow::Owned(out)
+    }
+}
+
+impl OwnedKey for SystemTime {
+    fn deserialize(mut bytes: &[u8]) -> Result<Self, Error> {
+        let key = Self::read_part(&mut bytes)?;
+        bytes.is_empty().then_some(key).ok_or(Error::InvalidKey)
+    }
+}
+
+macro_rules! tuple_key {
+    ($($part:ident),*) => {
+        impl<$($part: KeyPart),*> Key for ($($part,)*) {
+            #[allow(non_snake_case)]
+            fn serialize(&self) -> Cow<'_, [u8]> {
+                let ($($part,)*) = self;
+                let mut out = vec![];
+                $($part.write_part(&mut out);)*
+                Cow::Owned(out)
+            }
+        }
+
+        impl<$($part: KeyPart),*> OwnedKey for ($($part,)*) {
+            fn deserialize(mut bytes: &[u8]) -> Result<Self, Error> {
+                let key = ($($part::read_part(&mut bytes)?,)*);
+                bytes.is_empty().then_some(key).ok_or(Error::InvalidKey)
+            }
+        }
+    };
+}
+
+tuple_key!(A);
+tuple_key!(A, B);
+tuple_key!(A, B, C);
+tuple_key!(A, B, C, D);
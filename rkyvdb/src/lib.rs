@@ -1,18 +1,93 @@
-use std::{ops::Deref, sync::Arc};
+mod archived;
+#[cfg(feature = "async")]
+mod async_db;
+mod backend;
+mod checkpoint;
+mod dump;
+mod index;
+mod key;
+mod migration;
+mod stats;
+mod tuning;
 
-pub use rocksdb::Options;
+use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Deref,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+pub use archived::{ArchivedCollection, ArchivedRef};
+#[cfg(feature = "async")]
+pub use async_db::AsyncDatabase;
+use backend::{Backend, MemoryBackend, RocksDbBackend, Scan, ScanIter};
+pub use checkpoint::CheckpointDir;
+pub use dump::DumpFormat;
+use dump::Dumper;
+pub use index::IndexIter;
+pub use key::{CaseInsensitiveString, Key, KeyPart, OwnedKey};
+pub use migration::MigrationReport;
+use migration::Migrations;
+pub use rocksdb::{Direction, Options};
 use serde::{de::DeserializeOwned, Serialize};
+pub use stats::CollectionStats;
+pub use tuning::Tuning;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Collection is not registered in this DB")]
     CollectionNotRegistered,
+    #[error("Invalid key in DB")]
+    InvalidKey,
     #[error("RocksDB error")]
     RocksDB(#[from] rocksdb::Error),
     #[error("RMP decode error")]
     RmpDecode(#[from] rmp_serde::decode::Error),
     #[error("RMP encode error")]
     RmpEncode(#[from] rmp_serde::encode::Error),
+    #[error("Invalid rkyv archive in DB")]
+    InvalidArchive,
+    #[error("rkyv encode error")]
+    RkyvEncode,
+    #[error("Invalid schema version of {0}")]
+    InvalidSchemaVersion(&'static str),
+    #[error("No migration of {collection} from schema version {version}")]
+    MissingMigration {
+        collection: &'static str,
+        version: u32,
+    },
+    #[error("{collection} has schema version {stored}, newer than supported {supported}")]
+    SchemaTooNew {
+        collection: &'static str,
+        stored: u32,
+        supported: u32,
+    },
+    #[error("Index {0} is not declared in Collection::INDEXES")]
+    IndexNotDeclared(&'static str),
+    #[error("Index entry points to a missing record")]
+    BrokenIndex,
+    #[error("Checkpoint {0} not found")]
+    CheckpointNotFound(String),
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+    #[error("{0} are not supported")]
+    Unsupported(&'static str),
+    #[error("DB is opened read-only")]
+    ReadOnly,
+    #[error("{0} must be migrated or reindexed by opening the DB for writing first")]
+    NotUpToDate(&'static str),
+    #[error("JSON error")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid dump: {0}")]
+    InvalidDump(&'static str),
+    #[error("Dump of {collection} has schema version {dumped}, expected {supported}")]
+    DumpSchemaMismatch {
+        collection: &'static str,
+        dumped: u32,
+        supported: u32,
+    },
 }
 
 #[derive(Clone)]
@@ -26,12 +101,42 @@ impl Deref for Database {
     }
 }
 
+/// Number of locks keys are spread over. Read-modify-write cycles of keys sharing a lock are
+/// serialized, others run concurrently.
+const LOCK_STRIPES: usize = 64;
+
 pub struct DatabaseInner {
-    rocksdb: rocksdb::DB,
-    mutex: std::sync::Mutex<()>,
+    backend: Box<dyn Backend>,
+    /// Column families of registered collections and indexes.
+    cf_names: Vec<&'static str>,
+    locks: Vec<Mutex<()>>,
+    /// Collections included in `export`.
+    dumpers: Vec<Dumper>,
 }
 
 impl Database {
+    fn new(backend: Box<dyn Backend>, cf_names: Vec<&'static str>, dumpers: Vec<Dumper>) -> Self {
+        Database(Arc::new(DatabaseInner {
+            backend,
+            cf_names,
+            locks: (0..LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+            dumpers,
+        }))
+    }
+
+    pub fn batch(&self) -> WriteBatch {
+        WriteBatch {
+            db: self.clone(),
+            pending: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Makes a secondary instance see writes of the primary made since opening or the last
+    /// call.
+    pub fn catch_up_with_primary(&self) -> Result<(), Error> {
+        self.backend.catch_up()
+    }
+
     pub fn build() -> DatabaseBuilder {
         let mut opts = Options::default();
         opts.create_if_missing(true);
@@ -39,7 +144,7 @@ impl Database {
         opts.set_compression_type(rocksdb::DBCompressionType::Zstd);
         DatabaseBuilder {
             opts,
-            cf_descriptors: vec![],
+            ..Default::default()
         }
     }
 }
@@ -47,115 +152,606 @@ impl Database {
 #[derive(Default)]
 pub struct DatabaseBuilder {
     opts: Options,
-    cf_descriptors: Vec<rocksdb::ColumnFamilyDescriptor>,
+    column_families: Vec<(&'static str, Options)>,
+    schema_versions: Vec<(&'static str, u32)>,
+    migrations: Migrations,
+    reindexers: Vec<Reindexer>,
+    dumpers: Vec<Dumper>,
+    /// `Tuning::prefix_len` of collections which have one.
+    prefix_lens: HashMap<&'static str, usize>,
 }
 
+/// Collection name, its `index::signature` and a function rebuilding its indexes.
+type Reindexer = (&'static str, String, fn(&Database) -> Result<(), Error>);
+
 impl DatabaseBuilder {
-    pub fn add_collection<T: Collection>(mut self) -> Self {
-        self.cf_descriptors
-            .push(rocksdb::ColumnFamilyDescriptor::new(
-                T::CF_NAME.to_string(),
-                Options::default(),
-            ));
+    /// Registers `T`, configuring its column family as declared by `Collection::TUNING`. Keys
+    /// are serde types to be readable in dumps, see `Database::export`.
+    pub fn add_collection<T: Collection>(mut self) -> Self
+    where
+        T::KeyType: Serialize + DeserializeOwned,
+    {
+        if let Some(len) = T::TUNING.prefix_len {
+            self.prefix_lens.insert(T::CF_NAME, len);
+        }
+        self.add_collection_opt::<T>(T::TUNING.options())
+    }
+    /// Like `add_collection`, with column family options replacing `Collection::TUNING`. They
+    /// must not set a prefix extractor, use `Tuning::prefix_len` instead.
+    pub fn add_collection_opt<T: Collection>(mut self, opts: Options) -> Self
+    where
+        T::KeyType: Serialize + DeserializeOwned,
+    {
+        self.schema_versions.push((T::CF_NAME, T::SCHEMA_VERSION));
+        self.reindexers
+            .push((T::CF_NAME, index::signature::<T>(), index::reindex::<T>));
+        self.column_families.push((T::CF_NAME, opts));
+        self.dumpers.push(Dumper::new::<T>());
         self
     }
-    pub fn add_collection_opt<T: Collection>(mut self, opts: Options) -> Self {
-        self.cf_descriptors
-            .push(rocksdb::ColumnFamilyDescriptor::new(
-                T::CF_NAME.to_string(),
-                opts,
-            ));
+    pub fn add_archived_collection<T: ArchivedCollection>(mut self) -> Self {
+        if let Some(len) = T::TUNING.prefix_len {
+            self.prefix_lens.insert(T::CF_NAME, len);
+        }
+        self.column_families.push((T::CF_NAME, T::TUNING.options()));
         self
     }
     pub fn set_options(mut self, opts: Options) -> Self {
         self.opts = opts;
         self
     }
-    pub fn open(self, path: &str) -> Result<Database, rocksdb::Error> {
-        let db = rocksdb::DB::open_cf_descriptors(&self.opts, path, self.cf_descriptors)?;
-        Ok(Database(Arc::new(DatabaseInner {
-            rocksdb: db,
-            mutex: std::sync::Mutex::new(()),
-        })))
+    /// Registers conversion of `T` records stored with schema version `from_version` to the
+    /// next version. Returning `None` deletes the record.
+    pub fn add_migration<T, Old, F>(mut self, from_version: u32, migrate: F) -> Self
+    where
+        T: Collection,
+        Old: DeserializeOwned,
+        F: Fn(Old) -> Option<T> + Send + Sync + 'static,
+    {
+        self.migrations.add(
+            T::CF_NAME,
+            from_version,
+            Box::new(move |value| {
+                let old = rmp_serde::decode::from_slice(value)?;
+                migrate(old)
+                    .map(|value| rmp_serde::encode::to_vec(&value).map_err(Error::RmpEncode))
+                    .transpose()
+            }),
+        );
+        self
+    }
+    /// Reports what migrations `open` would run, without writing anything.
+    pub fn dry_run(self, path: &str) -> Result<Vec<MigrationReport>, Error> {
+        let existing = rocksdb::DB::list_cf(&self.opts, path).unwrap_or_default();
+        if existing.is_empty() {
+            return Ok(vec![]);
+        }
+        let db = RocksDbBackend::read_only(rocksdb::DB::open_cf_for_read_only(
+            &self.opts, path, &existing, false,
+        )?);
+        let mut reports = vec![];
+        for (cf_name, version) in self.schema_versions {
+            if existing.iter().any(|existing| existing == cf_name) {
+                reports.extend(self.migrations.migrate(&db, cf_name, version, true)?);
+            }
+        }
+        Ok(reports)
+    }
+    /// Opens the DB, migrating every collection to its `Collection::SCHEMA_VERSION` first and
+    /// rebuilding secondary indexes which were added or whose records were migrated.
+    pub fn open(self, path: &str) -> Result<Database, Error> {
+        let existing = rocksdb::DB::list_cf(&self.opts, path).unwrap_or_default();
+        let db = rocksdb::DB::open_cf_descriptors(&self.opts, path, self.cf_descriptors())?;
+        let backend = RocksDbBackend::new(db).with_prefix_lens(self.prefix_lens.clone());
+        self.init(Box::new(backend), &existing)
+    }
+    /// Opens the DB without writing to it, e.g. for analysis tools running next to the
+    /// process writing it. Reads see the DB as of opening.
+    pub fn open_read_only(self, path: &str) -> Result<Database, Error> {
+        let db = rocksdb::DB::open_cf_descriptors_read_only(
+            &self.opts,
+            path,
+            self.cf_descriptors(),
+            false,
+        )?;
+        let backend = RocksDbBackend::read_only(db).with_prefix_lens(self.prefix_lens.clone());
+        self.init_read_only(Box::new(backend))
+    }
+    /// Opens a read-only secondary instance of the DB at `path` which another process keeps
+    /// writing, storing its own logs in `secondary_path`. It sees new writes after
+    /// `Database::catch_up_with_primary`.
+    pub fn open_secondary(mut self, path: &str, secondary_path: &str) -> Result<Database, Error> {
+        // Required by RocksDB for secondary instances.
+        self.opts.set_max_open_files(-1);
+        let db = rocksdb::DB::open_cf_descriptors_as_secondary(
+            &self.opts,
+            path,
+            secondary_path,
+            self.cf_descriptors(),
+        )?;
+        let backend = RocksDbBackend::read_only(db).with_prefix_lens(self.prefix_lens.clone());
+        self.init_read_only(Box::new(backend))
+    }
+    fn cf_descriptors(&self) -> Vec<rocksdb::ColumnFamilyDescriptor> {
+        self.column_families
+            .iter()
+            .cloned()
+            .chain([(index::INDEX_CF_NAME, Options::default())])
+            .map(|(cf_name, opts)| rocksdb::ColumnFamilyDescriptor::new(cf_name, opts))
+            .collect()
+    }
+    fn cf_names(&self) -> Vec<&'static str> {
+        self.column_families
+            .iter()
+            .map(|(cf_name, _)| *cf_name)
+            .chain([index::INDEX_CF_NAME])
+            .collect()
+    }
+    /// Opens an empty DB kept in memory, e.g. to test code using collections without touching
+    /// the filesystem. Its contents are gone when the last clone of it is dropped.
+    pub fn open_in_memory(self) -> Result<Database, Error> {
+        let backend = MemoryBackend::new(self.cf_names());
+        self.init(Box::new(backend), &[])
+    }
+    /// Checks that a backend opened read-only needs no migrations or reindexing, which only
+    /// the writer may do.
+    fn init_read_only(self, backend: Box<dyn Backend>) -> Result<Database, Error> {
+        let cf_names = self.cf_names();
+        for (cf_name, version) in self.schema_versions {
+            let stored = migration::stored_version(&*backend, cf_name)?;
+            if stored > version {
+                return Err(Error::SchemaTooNew {
+                    collection: cf_name,
+                    stored,
+                    supported: version,
+                });
+            }
+            if stored < version {
+                return Err(Error::NotUpToDate(cf_name));
+            }
+        }
+        for (cf_name, signature, _) in self.reindexers {
+            if index::stored_signature(&*backend, cf_name)? != signature {
+                return Err(Error::NotUpToDate(cf_name));
+            }
+        }
+        Ok(Database::new(backend, cf_names, self.dumpers))
+    }
+    /// Migrates and reindexes collections of a just opened backend. `existing` are the names
+    /// of column families which were present before opening it.
+    fn init(self, backend: Box<dyn Backend>, existing: &[String]) -> Result<Database, Error> {
+        let cf_names = self.cf_names();
+        for (cf_name, version) in self.schema_versions {
+            if existing.iter().any(|existing| existing == cf_name) {
+                // Migrations write records without their index entries. Clearing the signature
+                // first makes the next open reindex even if this one stops before doing it, or
+                // resumes a migration whose changes were all written before stopping.
+                if migration::stored_version(&*backend, cf_name)? < version {
+                    index::set_signature(&*backend, cf_name, "")?;
+                }
+                self.migrations
+                    .migrate(&*backend, cf_name, version, false)?;
+            } else {
+                migration::set_version(&*backend, cf_name, version)?;
+            }
+        }
+        let db = Database::new(backend, cf_names, self.dumpers);
+        for (cf_name, signature, reindex) in self.reindexers {
+            if index::stored_signature(&*db.backend, cf_name)? != signature {
+                reindex(&db)?;
+                index::set_signature(&*db.backend, cf_name, &signature)?;
+            }
+        }
+        Ok(db)
+    }
+}
+
+/// Where collections are read from and written to: a `Database` or a `WriteBatch`.
+pub trait Store {
+    fn read<R>(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        reader: impl FnOnce(Option<&[u8]>) -> Result<R, Error>,
+    ) -> Result<R, Error>;
+
+    /// Like `read` for each of `keys`, returning results in the same order.
+    fn multi_read<R>(
+        &self,
+        cf_name: &'static str,
+        keys: &[&[u8]],
+        mut reader: impl FnMut(Option<&[u8]>) -> Result<R, Error>,
+    ) -> Result<Vec<R>, Error> {
+        keys.iter()
+            .map(|key| self.read(cf_name, key, &mut reader))
+            .collect()
+    }
+
+    fn write(&self, cf_name: &'static str, key: &[u8], value: Option<Vec<u8>>)
+        -> Result<(), Error>;
+
+    /// Replaces the value with the one returned by `updater`, atomically with respect to other
+    /// updates of the same key.
+    fn update(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        updater: impl FnOnce(Option<&[u8]>) -> Result<Option<Vec<u8>>, Error>,
+    ) -> Result<(), Error> {
+        self.update_with(cf_name, key, |value| Ok((updater(value)?, vec![])))
     }
+
+    /// Like `update`, also applying the returned writes of other keys atomically with the
+    /// value, e.g. index entries.
+    fn update_with(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        updater: impl FnOnce(Option<&[u8]>) -> Result<(Option<Vec<u8>>, Vec<Write>), Error>,
+    ) -> Result<(), Error>;
 }
 
-pub trait Key {
-    fn serialize(&self) -> &[u8];
+/// Write of a single key, see `Store::update_with`.
+pub struct Write {
+    pub cf_name: &'static str,
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
 }
 
-impl Key for () {
-    fn serialize(&self) -> &[u8] {
-        &[]
+impl Database {
+    fn stripe(&self, cf_name: &str, key: &[u8]) -> usize {
+        let mut hasher = DefaultHasher::new();
+        cf_name.hash(&mut hasher);
+        key.hash(&mut hasher);
+        hasher.finish() as usize % LOCK_STRIPES
+    }
+
+    fn lock(&self, cf_name: &str, key: &[u8]) -> MutexGuard<'_, ()> {
+        self.locks[self.stripe(cf_name, key)].lock().unwrap()
     }
 }
 
-impl Key for str {
-    fn serialize(&self) -> &[u8] {
-        self.as_bytes()
+impl Store for Database {
+    fn read<R>(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        reader: impl FnOnce(Option<&[u8]>) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        reader(self.backend.get(cf_name, key)?.as_deref())
+    }
+
+    fn multi_read<R>(
+        &self,
+        cf_name: &'static str,
+        keys: &[&[u8]],
+        mut reader: impl FnMut(Option<&[u8]>) -> Result<R, Error>,
+    ) -> Result<Vec<R>, Error> {
+        self.backend
+            .multi_get(cf_name, keys)?
+            .iter()
+            .map(|value| reader(value.as_deref()))
+            .collect()
+    }
+
+    fn write(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+    ) -> Result<(), Error> {
+        let _guard = self.lock(cf_name, key);
+        self.backend.write(vec![Write {
+            cf_name,
+            key: key.to_vec(),
+            value,
+        }])
+    }
+
+    fn update_with(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        updater: impl FnOnce(Option<&[u8]>) -> Result<(Option<Vec<u8>>, Vec<Write>), Error>,
+    ) -> Result<(), Error> {
+        let _guard = self.lock(cf_name, key);
+        let (value, mut writes) = updater(self.backend.get(cf_name, key)?.as_deref())?;
+        writes.push(Write {
+            cf_name,
+            key: key.to_vec(),
+            value,
+        });
+        self.backend.write(writes)
     }
 }
 
-pub struct CaseInsensitiveString(String);
+/// Writes to any collections which are committed to the database atomically. Reads through the
+/// batch see its own pending writes.
+///
+/// Pending writes are not isolated from concurrent writes to the database: whichever is
+/// written last wins.
+pub struct WriteBatch {
+    db: Database,
+    pending: RefCell<HashMap<&'static str, HashMap<Vec<u8>, Option<Vec<u8>>>>>,
+}
+
+impl WriteBatch {
+    /// Number of keys written since the last commit.
+    pub fn len(&self) -> usize {
+        self.pending.borrow().values().map(HashMap::len).sum()
+    }
 
-impl From<&str> for CaseInsensitiveString {
-    fn from(s: &str) -> Self {
-        Self(s.to_lowercase())
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn db(&self) -> &Database {
+        &self.db
+    }
+
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let db = &self.db;
+        let pending = self.pending.get_mut();
+        let mut writes = vec![];
+        let mut stripes = vec![];
+        for (&cf_name, values) in pending.iter() {
+            for (key, value) in values {
+                stripes.push(db.stripe(cf_name, key));
+                writes.push(Write {
+                    cf_name,
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        // Taken in a fixed order to avoid deadlocks with other commits.
+        stripes.sort_unstable();
+        stripes.dedup();
+        {
+            let _guards: Vec<_> = stripes
+                .into_iter()
+                .map(|stripe| db.locks[stripe].lock().unwrap())
+                .collect();
+            db.backend.write(writes)?;
+        }
+        pending.clear();
+        Ok(())
     }
 }
 
-impl From<&String> for CaseInsensitiveString {
-    fn from(s: &String) -> Self {
-        Self(s.to_lowercase())
+impl Store for WriteBatch {
+    fn read<R>(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        reader: impl FnOnce(Option<&[u8]>) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        if let Some(value) = self
+            .pending
+            .borrow()
+            .get(cf_name)
+            .and_then(|values| values.get(key))
+        {
+            return reader(value.as_deref());
+        }
+        self.db.read(cf_name, key, reader)
+    }
+
+    fn write(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+    ) -> Result<(), Error> {
+        if !self.db.backend.has_cf(cf_name) {
+            return Err(Error::CollectionNotRegistered);
+        }
+        self.pending
+            .borrow_mut()
+            .entry(cf_name)
+            .or_default()
+            .insert(key.to_vec(), value);
+        Ok(())
+    }
+
+    fn update_with(
+        &self,
+        cf_name: &'static str,
+        key: &[u8],
+        updater: impl FnOnce(Option<&[u8]>) -> Result<(Option<Vec<u8>>, Vec<Write>), Error>,
+    ) -> Result<(), Error> {
+        let (value, writes) = self.read(cf_name, key, updater)?;
+        for write in writes {
+            self.write(write.cf_name, &write.key, write.value)?;
+        }
+        self.write(cf_name, key, value)
     }
 }
 
-impl<'a> Key for CaseInsensitiveString {
-    fn serialize(&self) -> &[u8] {
-        self.0.as_bytes()
+fn decode_value<T: DeserializeOwned>(value: Option<&[u8]>) -> Result<Option<T>, Error> {
+    value
+        .map(|value| rmp_serde::decode::from_slice(value).map_err(Error::RmpDecode))
+        .transpose()
+}
+
+fn iter_collection<'a, T: Collection>(
+    scan: Scan<'_>,
+    db: &'a Database,
+) -> Result<Iter<'a, T>, Error> {
+    Ok(Iter {
+        inner: db.backend.scan(T::CF_NAME, scan)?,
+        collection: PhantomData,
+    })
+}
+
+/// Deserialized `(key, value)` pairs of a collection in key order.
+pub struct Iter<'a, T> {
+    inner: ScanIter<'a>,
+    collection: PhantomData<T>,
+}
+
+impl<'a, T: Collection> Iterator for Iter<'a, T> {
+    type Item = Result<(T::KeyType, T), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = match self.inner.next()? {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(T::KeyType::deserialize(&key).and_then(|key| {
+            let value = rmp_serde::decode::from_slice(&value).map_err(Error::RmpDecode)?;
+            Ok((key, value))
+        }))
     }
 }
 
 pub trait Collection: Serialize + DeserializeOwned + Sized {
-    type KeyType: Key;
+    type KeyType: OwnedKey;
     const CF_NAME: &'static str;
+    /// Bumped whenever the stored format changes, together with registering a migration from
+    /// the previous version in `DatabaseBuilder::add_migration`.
+    const SCHEMA_VERSION: u32 = 0;
+
+    /// RocksDB settings of the collection's column family, applied by
+    /// `DatabaseBuilder::add_collection`.
+    const TUNING: Tuning = Tuning::DEFAULT;
 
-    fn get<K: Into<Self::KeyType>>(key: K, db: &Database) -> Result<Option<Self>, Error> {
+    /// Names of secondary indexes kept up to date by `put`, `delete` and `modify`.
+    const INDEXES: &'static [&'static str] = &[];
+
+    /// Values of the record in `index`, if it's indexed there. Index scans are ordered by
+    /// value bytewise, so values of an index should have a fixed width, e.g. big-endian
+    /// integers.
+    fn index_values(&self, _index: &str) -> Vec<Vec<u8>> {
+        vec![]
+    }
+
+    fn get<K: Into<Self::KeyType>>(key: K, db: &impl Store) -> Result<Option<Self>, Error> {
         let key = key.into();
-        let cf = db
-            .rocksdb
-            .cf_handle(Self::CF_NAME)
-            .ok_or(Error::CollectionNotRegistered)?;
-        db.rocksdb.get_pinned_cf(cf, key.serialize())?.map_or(Ok(None), |value| {
-            rmp_serde::decode::from_slice(&value).map_err(Error::RmpDecode)
+        db.read(Self::CF_NAME, &key.serialize(), decode_value)
+    }
+
+    /// Records of `keys` in the same order, read in one batch.
+    fn multi_get<K: Into<Self::KeyType>>(
+        keys: impl IntoIterator<Item = K>,
+        db: &impl Store,
+    ) -> Result<Vec<Option<Self>>, Error> {
+        let keys: Vec<Self::KeyType> = keys.into_iter().map(Into::into).collect();
+        let keys: Vec<_> = keys.iter().map(Key::serialize).collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|key| &**key).collect();
+        db.multi_read(Self::CF_NAME, &keys, decode_value)
+    }
+
+    fn put<K: Into<Self::KeyType>>(key: K, value: &Self, db: &impl Store) -> Result<(), Error> {
+        let key: Self::KeyType = key.into();
+        let encoded = rmp_serde::encode::to_vec(value).map_err(Error::RmpEncode)?;
+        if Self::INDEXES.is_empty() {
+            return db.write(Self::CF_NAME, &key.serialize(), Some(encoded));
+        }
+        let key = key.serialize();
+        db.update_with(Self::CF_NAME, &key, |old_value| {
+            let old_value: Option<Self> = decode_value(old_value)?;
+            let writes = index::index_writes::<Self>(
+                &key,
+                &index::index_values(old_value.as_ref()),
+                &index::index_values(Some(value)),
+            );
+            Ok((Some(encoded), writes))
+        })
+    }
+
+    fn delete<K: Into<Self::KeyType>>(key: K, db: &impl Store) -> Result<(), Error> {
+        let key: Self::KeyType = key.into();
+        if Self::INDEXES.is_empty() {
+            return db.write(Self::CF_NAME, &key.serialize(), None);
+        }
+        let key = key.serialize();
+        db.update_with(Self::CF_NAME, &key, |old_value| {
+            let old_value: Option<Self> = decode_value(old_value)?;
+            let writes = index::index_writes::<Self>(
+                &key,
+                &index::index_values(old_value.as_ref()),
+                &index::index_values::<Self>(None),
+            );
+            Ok((None, writes))
         })
     }
 
     fn modify<K: Into<Self::KeyType>>(
         key: K,
-        db: &Database,
+        db: &impl Store,
         modifier: impl FnOnce(Option<Self>) -> Option<Self>,
     ) -> Result<(), Error> {
-        let cf = db
-            .rocksdb
-            .cf_handle(Self::CF_NAME)
-            .ok_or(Error::CollectionNotRegistered)?;
         let key: Self::KeyType = key.into();
-        let serialized_key = key.serialize();
-        let _guard = db.mutex.lock().unwrap();
-        let old_value = db.rocksdb.get_pinned_cf(cf, serialized_key)?.map_or(Ok(None), |value| {
-            rmp_serde::decode::from_slice(&value).map_err(Error::RmpDecode)
-        })?;
-        let value = modifier(old_value);
-        if let Some(value) = value {
-            db.rocksdb.put_cf(
-                cf,
-                serialized_key,
-                rmp_serde::encode::to_vec(&value).map_err(Error::RmpEncode)?,
-            )?;
-        } else {
-            db.rocksdb.delete_cf(cf, serialized_key)?;
-        }
-        Ok(())
+        let key = key.serialize();
+        db.update_with(Self::CF_NAME, &key, |old_value| {
+            let old_value: Option<Self> = decode_value(old_value)?;
+            let old_indexes = index::index_values(old_value.as_ref());
+            let value = modifier(old_value);
+            let writes = index::index_writes::<Self>(
+                &key,
+                &old_indexes,
+                &index::index_values(value.as_ref()),
+            );
+            let value = value
+                .map(|value| rmp_serde::encode::to_vec(&value).map_err(Error::RmpEncode))
+                .transpose()?;
+            Ok((value, writes))
+        })
+    }
+
+    /// Records with a value in `index` starting with `value_prefix`, ordered by the value.
+    fn index_scan<'a>(
+        index: &'static str,
+        value_prefix: &[u8],
+        direction: Direction,
+        db: &'a Database,
+    ) -> Result<IndexIter<'a, Self>, Error> {
+        index::scan(index, value_prefix, direction, db)
+    }
+
+    fn iter(db: &Database) -> Result<Iter<'_, Self>, Error> {
+        iter_collection::<Self>(Scan::all(Direction::Forward), db)
+    }
+
+    fn iter_rev(db: &Database) -> Result<Iter<'_, Self>, Error> {
+        iter_collection::<Self>(Scan::all(Direction::Reverse), db)
+    }
+
+    /// Iterates starting at `key`, or at the nearest key after it in the given direction.
+    fn iter_from<K: Into<Self::KeyType>>(
+        key: K,
+        direction: Direction,
+        db: &Database,
+    ) -> Result<Iter<'_, Self>, Error> {
+        let key: Self::KeyType = key.into();
+        let key = key.serialize();
+        let scan = Scan {
+            from: Some(&key),
+            direction,
+            prefix: &[],
+        };
+        iter_collection::<Self>(scan, db)
+    }
+
+    /// Iterates over keys starting with `prefix`, e.g. user ids for autocomplete.
+    fn prefix_iter<K: Into<Self::KeyType>>(
+        prefix: K,
+        db: &Database,
+    ) -> Result<Iter<'_, Self>, Error> {
+        let prefix: Self::KeyType = prefix.into();
+        Self::prefix_iter_by(&prefix, db)
+    }
+
+    /// Iterates over keys whose encoding starts with the one of `prefix`, e.g. over composite
+    /// keys `(root, user)` with a given root by passing `(root,)`.
+    fn prefix_iter_by<P: Key + ?Sized>(prefix: &P, db: &Database) -> Result<Iter<'_, Self>, Error> {
+        let prefix = prefix.serialize();
+        let scan = Scan {
+            from: None,
+            direction: Direction::Forward,
+            prefix: &prefix,
+        };
+        iter_collection::<Self>(scan, db)
     }
 }
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use crate::{
+    backend::{Backend, Scan, METADATA_CF_NAME},
+    Direction, Error, Write,
+};
+
+/// Records migrated between saving progress, so that an interrupted migration resumes.
+const CHUNK_SIZE: u64 = 10_000;
+
+pub(crate) type MigrateFn = Box<dyn Fn(&[u8]) -> Result<Option<Vec<u8>>, Error> + Send + Sync>;
+
+/// Outcome of migrating a single collection to its current schema version.
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+    pub collection: &'static str,
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Records read, not counting ones migrated before an interruption.
+    pub records: u64,
+    /// Records which were (or in a dry run would be) rewritten or deleted.
+    pub changed: u64,
+}
+
+fn version_key(cf_name: &str) -> Vec<u8> {
+    format!("rkyvdb/schema_version/{cf_name}").into_bytes()
+}
+
+/// Last key migrated by an unfinished migration of the collection.
+fn progress_key(cf_name: &str) -> Vec<u8> {
+    format!("rkyvdb/migration_progress/{cf_name}").into_bytes()
+}
+
+fn version_write(cf_name: &str, version: u32) -> Write {
+    Write {
+        cf_name: METADATA_CF_NAME,
+        key: version_key(cf_name),
+        value: Some(version.to_le_bytes().to_vec()),
+    }
+}
+
+pub(crate) fn set_version(db: &dyn Backend, cf_name: &str, version: u32) -> Result<(), Error> {
+    db.write(vec![version_write(cf_name, version)])
+}
+
+pub(crate) fn stored_version(db: &dyn Backend, cf_name: &'static str) -> Result<u32, Error> {
+    match db.get(METADATA_CF_NAME, &version_key(cf_name))? {
+        Some(bytes) => Ok(u32::from_le_bytes(
+            (&*bytes)
+                .try_into()
+                .map_err(|_| Error::InvalidSchemaVersion(cf_name))?,
+        )),
+        // Collections written before versioning.
+        None => Ok(0),
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Migrations(HashMap<(&'static str, u32), MigrateFn>);
+
+impl Migrations {
+    pub(crate) fn add(&mut self, cf_name: &'static str, from_version: u32, migrate: MigrateFn) {
+        self.0.insert((cf_name, from_version), migrate);
+    }
+
+    fn apply(
+        &self,
+        cf_name: &'static str,
+        from_version: u32,
+        to_version: u32,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let mut value = value.to_vec();
+        for version in from_version..to_version {
+            let migrate = self
+                .0
+                .get(&(cf_name, version))
+                .ok_or(Error::MissingMigration {
+                    collection: cf_name,
+                    version,
+                })?;
+            match migrate(&value)? {
+                Some(migrated) => value = migrated,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// Rewrites every record of the collection from its stored schema version to `to_version`.
+    /// Returns `None` if it's already up to date.
+    pub(crate) fn migrate(
+        &self,
+        db: &dyn Backend,
+        cf_name: &'static str,
+        to_version: u32,
+        dry_run: bool,
+    ) -> Result<Option<MigrationReport>, Error> {
+        let from_version = stored_version(db, cf_name)?;
+        if from_version > to_version {
+            return Err(Error::SchemaTooNew {
+                collection: cf_name,
+                stored: from_version,
+                supported: to_version,
+            });
+        }
+        if from_version == to_version {
+            return Ok(None);
+        }
+        let resume_after = db
+            .get(METADATA_CF_NAME, &progress_key(cf_name))?
+            .map(|key| key.to_vec());
+        let scan = Scan {
+            from: resume_after.as_deref(),
+            direction: Direction::Forward,
+            prefix: &[],
+        };
+        let mut report = MigrationReport {
+            collection: cf_name,
+            from_version,
+            to_version,
+            records: 0,
+            changed: 0,
+        };
+        let mut batch = vec![];
+        for entry in db.scan(cf_name, scan)? {
+            let (key, value) = entry?;
+            if resume_after.as_deref() == Some(&*key) {
+                continue;
+            }
+            report.records += 1;
+            let migrated = self.apply(cf_name, from_version, to_version, &value)?;
+            if migrated.as_deref() != Some(&*value) {
+                report.changed += 1;
+                // A dry run only counts changes, so that it doesn't hold the whole collection.
+                if !dry_run {
+                    batch.push(Write {
+                        cf_name,
+                        key: key.to_vec(),
+                        value: migrated,
+                    });
+                }
+            }
+            if !dry_run && report.records % CHUNK_SIZE == 0 {
+                batch.push(Write {
+                    cf_name: METADATA_CF_NAME,
+                    key: progress_key(cf_name),
+                    value: Some(key.to_vec()),
+                });
+                db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        if !dry_run {
+            batch.push(version_write(cf_name, to_version));
+            batch.push(Write {
+                cf_name: METADATA_CF_NAME,
+                key: progress_key(cf_name),
+                value: None,
+            });
+            db.write(batch)?;
+        }
+        Ok(Some(report))
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{Database, Error};
+
+/// RocksDB's estimates for the column family of a collection.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionStats {
+    pub estimated_keys: u64,
+    /// Size of the collection's files on disk.
+    pub sst_size: u64,
+    /// Bytes compaction has to rewrite to bring the collection's levels back within their
+    /// target sizes.
+    pub pending_compaction_bytes: u64,
+}
+
+impl Database {
+    /// Column families of registered collections, plus the one holding secondary indexes.
+    pub fn cf_names(&self) -> &[&'static str] {
+        &self.cf_names
+    }
+
+    pub fn collection_stats(&self, cf_name: &str) -> Result<CollectionStats, Error> {
+        self.backend.stats(cf_name)
+    }
+
+    /// Compacts all files of the collection, e.g. to reclaim space and speed up reads after
+    /// rewriting most of it. Blocks until done, which can take minutes for large collections.
+    pub fn compact(&self, cf_name: &str) -> Result<(), Error> {
+        self.backend.compact(cf_name)
+    }
+}
@@ -0,0 +1,54 @@
+use rocksdb::{BlockBasedOptions, Cache, DBCompressionType, Options, SliceTransform};
+
+/// RocksDB settings of a collection's column family, see `Collection::TUNING`. Unset fields
+/// keep RocksDB's defaults. In-memory databases ignore them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    /// Bits per key of a bloom filter, which lets point lookups of missing keys skip reading
+    /// blocks. 10 bits give about 1% false positives.
+    pub bloom_filter_bits: Option<f64>,
+    /// Size in bytes of an LRU block cache used by this collection only.
+    pub block_cache_size: Option<usize>,
+    /// Length of key prefixes RocksDB builds filters for, e.g. the fixed-width first field of
+    /// composite keys iterated with `Collection::prefix_iter_by`. Scans with a shorter prefix
+    /// still see every key, but don't benefit from the filters.
+    pub prefix_len: Option<usize>,
+    /// zstd level new files of the collection are compressed with.
+    pub compression_level: Option<i32>,
+}
+
+impl Tuning {
+    pub const DEFAULT: Tuning = Tuning {
+        bloom_filter_bits: None,
+        block_cache_size: None,
+        prefix_len: None,
+        compression_level: None,
+    };
+
+    pub fn options(&self) -> Options {
+        let mut opts = Options::default();
+        let mut table = BlockBasedOptions::default();
+        if let Some(bits) = self.bloom_filter_bits {
+            table.set_bloom_filter(bits, false);
+        }
+        if let Some(size) = self.block_cache_size {
+            table.set_block_cache(&Cache::new_lru_cache(size));
+        }
+        opts.set_block_based_table_factory(&table);
+        if let Some(len) = self.prefix_len {
+            opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(len));
+        }
+        if let Some(level) = self.compression_level {
+            opts.set_compression_type(DBCompressionType::Zstd);
+            // Window bits, strategy and dictionary size are RocksDB's defaults.
+            opts.set_compression_options(-14, level, 0, 0);
+        }
+        opts
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning::DEFAULT
+    }
+}
@@ -0,0 +1,96 @@
+use rkyvdb::{ArchivedCollection, Database, Tuning};
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+#[archive(check_bytes)]
+struct Profile {
+    name: String,
+    games: u32,
+}
+
+impl ArchivedCollection for Profile {
+    type KeyType = u64;
+    const CF_NAME: &'static str = "profiles";
+    const TUNING: Tuning = Tuning {
+        bloom_filter_bits: Some(10.0),
+        ..Tuning::DEFAULT
+    };
+}
+
+/// Names of varying length, so that RocksDB returns values at various offsets in its blocks.
+fn profile(id: u64) -> Profile {
+    Profile {
+        name: "x".repeat(id as usize),
+        games: id as u32,
+    }
+}
+
+const PROFILES: u64 = 64;
+
+/// Reads back every profile in place, returning how many were borrowed from the backend.
+fn check_profiles(db: &Database) -> u64 {
+    let mut borrowed = 0;
+    for id in 0..PROFILES {
+        let archived = Profile::get_archived(id, db).unwrap().unwrap();
+        assert_eq!(archived.name.as_str(), profile(id).name);
+        assert_eq!(archived.games, id as u32);
+        borrowed += archived.is_borrowed() as u64;
+    }
+    assert!(Profile::get_archived(PROFILES, db).unwrap().is_none());
+    borrowed
+}
+
+fn check_modify(db: &Database) {
+    Profile::modify(PROFILES, db, |profile| {
+        assert_eq!(profile, None);
+        Some(Profile {
+            name: "alice".to_string(),
+            games: 1,
+        })
+    })
+    .unwrap();
+    Profile::modify(PROFILES, db, |profile| {
+        let mut profile = profile.unwrap();
+        profile.games += 1;
+        Some(profile)
+    })
+    .unwrap();
+    assert_eq!(
+        Profile::get(PROFILES, db).unwrap(),
+        Some(Profile {
+            name: "alice".to_string(),
+            games: 2
+        })
+    );
+    Profile::modify(PROFILES, db, |_| None).unwrap();
+    assert_eq!(Profile::get(PROFILES, db).unwrap(), None);
+}
+
+#[test]
+fn in_memory() {
+    let db = Database::build()
+        .add_archived_collection::<Profile>()
+        .open_in_memory()
+        .unwrap();
+    for id in 0..PROFILES {
+        Profile::put(id, &profile(id), &db).unwrap();
+    }
+    check_profiles(&db);
+    check_modify(&db);
+}
+
+#[test]
+fn rocksdb_copies_unaligned_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::build()
+        .add_archived_collection::<Profile>()
+        .open(dir.path().join("db").to_str().unwrap())
+        .unwrap();
+    for id in 0..PROFILES {
+        Profile::put(id, &profile(id), &db).unwrap();
+    }
+    check_profiles(&db);
+    // Values pinned in SST blocks start wherever the previous entry ended.
+    db.compact("profiles").unwrap();
+    assert!(check_profiles(&db) < PROFILES);
+    check_modify(&db);
+}
@@ -0,0 +1,64 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rkyvdb::AsyncDatabase;
+
+mod common;
+
+use common::Counter;
+
+fn open(max_blocking: usize) -> AsyncDatabase {
+    AsyncDatabase::new(common::open(), max_blocking)
+}
+
+#[tokio::test]
+async fn get_put_delete() {
+    let db = open(4);
+    db.put("key", Counter(1)).await.unwrap();
+    assert_eq!(db.get::<Counter>("KEY").await.unwrap(), Some(Counter(1)));
+    db.delete::<Counter>("key").await.unwrap();
+    assert_eq!(db.get::<Counter>("key").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn multi_get_keeps_key_order() {
+    let db = open(4);
+    db.put("a", Counter(1)).await.unwrap();
+    db.put("c", Counter(3)).await.unwrap();
+    assert_eq!(
+        db.multi_get::<Counter, _>(["c", "b", "a"]).await.unwrap(),
+        [Some(Counter(3)), None, Some(Counter(1))]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn calls_beyond_the_limit_wait() {
+    let db = open(2);
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let (db, in_flight, max_in_flight) =
+                (db.clone(), in_flight.clone(), max_in_flight.clone());
+            tokio::spawn(async move {
+                db.run(move |_| {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                })
+                .await
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert!(max_in_flight.load(Ordering::SeqCst) <= 2);
+}
@@ -0,0 +1,68 @@
+use rkyvdb::{CheckpointDir, Collection, Database, Error};
+
+mod common;
+
+use common::{path, Counter};
+
+fn open(path: &str) -> Database {
+    common::builder().open(path).unwrap()
+}
+
+#[test]
+fn prune_and_restore() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = open(&path(&dir, "db"));
+    let checkpoints = CheckpointDir::new(dir.path().join("checkpoints"));
+    for month in 1..=3 {
+        Counter::put("alice", &Counter(month), &db).unwrap();
+        checkpoints
+            .create(&db, &format!("after-2023-0{month}"))
+            .unwrap();
+    }
+    checkpoints.create(&db, "manual").unwrap();
+    Counter::put("alice", &Counter(4), &db).unwrap();
+    assert_eq!(
+        checkpoints.list().unwrap(),
+        ["after-2023-01", "after-2023-02", "after-2023-03", "manual"]
+    );
+
+    assert_eq!(
+        checkpoints.prune("after-", 1).unwrap(),
+        ["after-2023-01", "after-2023-02"]
+    );
+    assert_eq!(checkpoints.list().unwrap(), ["after-2023-03", "manual"]);
+
+    checkpoints
+        .restore("after-2023-03", &dir.path().join("restored"))
+        .unwrap();
+    let restored = open(&path(&dir, "restored"));
+    assert_eq!(Counter::get("alice", &restored).unwrap(), Some(Counter(3)));
+    // Writes to the restored DB leave the checkpoint untouched.
+    Counter::put("alice", &Counter(5), &restored).unwrap();
+    drop(restored);
+    checkpoints
+        .restore("after-2023-03", &dir.path().join("again"))
+        .unwrap();
+    let again = open(&path(&dir, "again"));
+    assert_eq!(Counter::get("alice", &again).unwrap(), Some(Counter(3)));
+}
+
+#[test]
+fn restore_missing_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoints = CheckpointDir::new(dir.path().join("checkpoints"));
+    assert!(matches!(
+        checkpoints.restore("after-2023-01", &dir.path().join("restored")),
+        Err(Error::CheckpointNotFound(name)) if name == "after-2023-01"
+    ));
+}
+
+#[test]
+fn in_memory_checkpoints_are_unsupported() {
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoints = CheckpointDir::new(dir.path().join("checkpoints"));
+    assert!(matches!(
+        checkpoints.create(&common::open(), "manual"),
+        Err(Error::Unsupported(_))
+    ));
+}
@@ -0,0 +1,52 @@
+//! Collections and helpers shared by the integration tests. Each test uses some of them only.
+#![allow(dead_code)]
+
+use rkyvdb::{CaseInsensitiveString, Collection, Database, DatabaseBuilder, Error};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Counter(pub u64);
+
+impl Collection for Counter {
+    type KeyType = CaseInsensitiveString;
+    const CF_NAME: &'static str = "counters";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Score(pub u32);
+
+impl Collection for Score {
+    type KeyType = CaseInsensitiveString;
+    const CF_NAME: &'static str = "scores";
+    const INDEXES: &'static [&'static str] = &["score"];
+
+    fn index_values(&self, _index: &str) -> Vec<Vec<u8>> {
+        vec![self.0.to_be_bytes().to_vec()]
+    }
+}
+
+/// Builder of a DB with `Counter` and `Score`, to add a test's own collections to.
+pub fn builder() -> DatabaseBuilder {
+    Database::build()
+        .add_collection::<Counter>()
+        .add_collection::<Score>()
+}
+
+/// In-memory DB with `Counter` and `Score`.
+pub fn open() -> Database {
+    builder().open_in_memory().unwrap()
+}
+
+/// Path of a DB named `name` in `dir`.
+pub fn path(dir: &tempfile::TempDir, name: &str) -> String {
+    dir.path().join(name).to_str().unwrap().to_string()
+}
+
+/// Keys of iterated records, in lowercase.
+pub fn keys<T>(
+    entries: impl Iterator<Item = Result<(CaseInsensitiveString, T), Error>>,
+) -> Vec<String> {
+    entries
+        .map(|entry| entry.unwrap().0.as_str().to_string())
+        .collect()
+}
@@ -0,0 +1,124 @@
+use std::{sync::Barrier, thread};
+
+use rkyvdb::{CaseInsensitiveString, Collection, Database};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::{path, Counter};
+
+const THREADS: u64 = 8;
+const INCREMENTS: u64 = 1_000;
+
+#[derive(Serialize, Deserialize)]
+struct OtherCounter(u64);
+
+impl Collection for OtherCounter {
+    type KeyType = CaseInsensitiveString;
+    const CF_NAME: &'static str = "other_counters";
+}
+
+fn open() -> (tempfile::TempDir, Database) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::builder()
+        .add_collection::<OtherCounter>()
+        .open(&path(&dir, "db"))
+        .unwrap();
+    (dir, db)
+}
+
+fn increment<T: Collection>(key: &str, db: &Database, wrap: fn(u64) -> T, unwrap: fn(T) -> u64)
+where
+    T::KeyType: for<'a> From<&'a str>,
+{
+    T::modify(key, db, |value| Some(wrap(value.map_or(0, unwrap) + 1))).unwrap();
+}
+
+/// Runs `work(thread_index)` on all threads at once.
+fn run_threads(work: impl Fn(u64) + Sync) {
+    let barrier = Barrier::new(THREADS as usize);
+    thread::scope(|scope| {
+        for thread_index in 0..THREADS {
+            let (barrier, work) = (&barrier, &work);
+            scope.spawn(move || {
+                barrier.wait();
+                work(thread_index);
+            });
+        }
+    });
+}
+
+#[test]
+fn no_lost_updates_on_single_key() {
+    let (_dir, db) = open();
+    run_threads(|_| {
+        for _ in 0..INCREMENTS {
+            increment("key", &db, Counter, |counter| counter.0);
+        }
+    });
+    assert_eq!(
+        Counter::get("key", &db).unwrap().unwrap().0,
+        THREADS * INCREMENTS
+    );
+}
+
+#[test]
+fn no_lost_updates_on_many_keys() {
+    let (_dir, db) = open();
+    run_threads(|thread_index| {
+        for index in 0..INCREMENTS {
+            increment(
+                &format!("key{}", (index + thread_index) % 100),
+                &db,
+                Counter,
+                |c| c.0,
+            );
+        }
+    });
+    let total: u64 = (0..100)
+        .map(|key| {
+            Counter::get(format!("key{key}").as_str(), &db)
+                .unwrap()
+                .unwrap()
+                .0
+        })
+        .sum();
+    assert_eq!(total, THREADS * INCREMENTS);
+}
+
+#[test]
+fn keys_are_case_insensitive_under_contention() {
+    let (_dir, db) = open();
+    run_threads(|thread_index| {
+        let key = if thread_index % 2 == 0 { "Key" } else { "kEY" };
+        for _ in 0..INCREMENTS {
+            increment(key, &db, Counter, |counter| counter.0);
+        }
+    });
+    assert_eq!(
+        Counter::get("key", &db).unwrap().unwrap().0,
+        THREADS * INCREMENTS
+    );
+}
+
+#[test]
+fn same_key_in_different_collections_is_independent() {
+    let (_dir, db) = open();
+    run_threads(|thread_index| {
+        for _ in 0..INCREMENTS {
+            if thread_index % 2 == 0 {
+                increment("key", &db, Counter, |counter| counter.0);
+            } else {
+                increment("key", &db, OtherCounter, |counter| counter.0);
+            }
+        }
+    });
+    assert_eq!(
+        Counter::get("key", &db).unwrap().unwrap().0,
+        THREADS / 2 * INCREMENTS
+    );
+    assert_eq!(
+        OtherCounter::get("key", &db).unwrap().unwrap().0,
+        THREADS / 2 * INCREMENTS
+    );
+}
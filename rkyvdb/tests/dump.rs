@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use rkyvdb::{Collection, Database, Direction, DumpFormat, Error};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::{Counter, Score};
+
+/// Totals per month, whose integer keys are strings in JSON.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Monthly(BTreeMap<u32, u64>);
+
+impl Collection for Monthly {
+    type KeyType = ();
+    const CF_NAME: &'static str = "monthly";
+    const SCHEMA_VERSION: u32 = 2;
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Game(u32);
+
+impl Collection for Game {
+    type KeyType = (String, i64);
+    const CF_NAME: &'static str = "games";
+}
+
+fn open() -> Database {
+    common::builder()
+        .add_collection::<Monthly>()
+        .add_collection::<Game>()
+        .open_in_memory()
+        .unwrap()
+}
+
+fn monthly() -> Monthly {
+    Monthly(BTreeMap::from([(1, 8)]))
+}
+
+fn round_trip(format: DumpFormat) {
+    let db = open();
+    Counter::put("alice", &Counter(1), &db).unwrap();
+    Score::put("alice", &Score(3), &db).unwrap();
+    Score::put("bob", &Score(5), &db).unwrap();
+    Monthly::put((), &monthly(), &db).unwrap();
+    Game::put(("alice".to_string(), -1), &Game(3), &db).unwrap();
+    let counts = [("counters", 1), ("scores", 2), ("monthly", 1), ("games", 1)];
+    let mut dump = vec![];
+    assert_eq!(db.export(&mut dump, format).unwrap(), counts);
+
+    let imported = open();
+    assert_eq!(imported.import(&dump[..], format).unwrap(), counts);
+    assert_eq!(Counter::get("alice", &imported).unwrap(), Some(Counter(1)));
+    assert_eq!(Score::get("alice", &imported).unwrap(), Some(Score(3)));
+    assert_eq!(Score::get("bob", &imported).unwrap(), Some(Score(5)));
+    assert_eq!(Monthly::get((), &imported).unwrap(), Some(monthly()));
+    assert_eq!(
+        Game::get(("alice".to_string(), -1), &imported).unwrap(),
+        Some(Game(3))
+    );
+    let by_score: Vec<_> = Score::index_scan("score", &[], Direction::Reverse, &imported)
+        .unwrap()
+        .map(|entry| entry.unwrap().1)
+        .collect();
+    assert_eq!(by_score, [Score(5), Score(3)]);
+}
+
+#[test]
+fn json_round_trip() {
+    round_trip(DumpFormat::Json);
+}
+
+#[test]
+fn message_pack_round_trip() {
+    round_trip(DumpFormat::MessagePack);
+}
+
+#[test]
+fn json_is_one_entry_per_line_with_decoded_keys() {
+    let db = open();
+    Counter::put("Alice", &Counter(1), &db).unwrap();
+    Monthly::put((), &monthly(), &db).unwrap();
+    Game::put(("alice".to_string(), -1), &Game(3), &db).unwrap();
+    let mut dump = vec![];
+    db.export(&mut dump, DumpFormat::Json).unwrap();
+    assert_eq!(
+        String::from_utf8(dump).unwrap(),
+        concat!(
+            "{\"collection\":\"counters\",\"schema_version\":0}\n",
+            "{\"key\":\"alice\",\"value\":1}\n",
+            "{\"collection\":\"scores\",\"schema_version\":0}\n",
+            "{\"collection\":\"monthly\",\"schema_version\":2}\n",
+            "{\"key\":null,\"value\":{\"1\":8}}\n",
+            "{\"collection\":\"games\",\"schema_version\":0}\n",
+            "{\"key\":[\"alice\",-1],\"value\":3}\n",
+        )
+    );
+}
+
+#[test]
+fn import_rejects_other_schema_versions() {
+    let dump = "{\"collection\":\"monthly\",\"schema_version\":1}\n";
+    assert!(matches!(
+        open().import(dump.as_bytes(), DumpFormat::Json),
+        Err(Error::DumpSchemaMismatch {
+            collection: "monthly",
+            dumped: 1,
+            supported: 2,
+        })
+    ));
+}
+
+#[test]
+fn import_rejects_unknown_collections() {
+    let dump = "{\"collection\":\"moves\",\"schema_version\":0}\n";
+    assert!(matches!(
+        open().import(dump.as_bytes(), DumpFormat::Json),
+        Err(Error::CollectionNotRegistered)
+    ));
+}
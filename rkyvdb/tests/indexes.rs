@@ -0,0 +1,89 @@
+use rkyvdb::{CaseInsensitiveString, Collection, Database, Direction, Error};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::{keys, path, Score};
+
+/// `Score` as stored before its index was declared.
+#[derive(Serialize, Deserialize)]
+struct UnindexedScore(u32);
+
+impl Collection for UnindexedScore {
+    type KeyType = CaseInsensitiveString;
+    const CF_NAME: &'static str = "scores";
+}
+
+fn by_score(db: &Database) -> Vec<String> {
+    keys(Score::index_scan("score", &[], Direction::Forward, db).unwrap())
+}
+
+fn with_score(score: u32, db: &Database) -> Vec<String> {
+    keys(Score::index_scan("score", &score.to_be_bytes(), Direction::Forward, db).unwrap())
+}
+
+#[test]
+fn writes_update_index_entries() {
+    let db = common::open();
+    Score::put("alice", &Score(2), &db).unwrap();
+    Score::put("bob", &Score(1), &db).unwrap();
+    Score::modify("bob", &db, |_| Some(Score(3))).unwrap();
+    assert_eq!(by_score(&db), ["alice", "bob"]);
+    assert!(with_score(1, &db).is_empty());
+    assert_eq!(with_score(3, &db), ["bob"]);
+    Score::delete("alice", &db).unwrap();
+    Score::modify("bob", &db, |_| None).unwrap();
+    assert!(by_score(&db).is_empty());
+}
+
+#[test]
+fn batched_writes_update_index_entries() {
+    let db = common::open();
+    let mut batch = db.batch();
+    Score::put("alice", &Score(2), &batch).unwrap();
+    Score::put("bob", &Score(1), &batch).unwrap();
+    assert!(by_score(&db).is_empty());
+    batch.commit().unwrap();
+    assert_eq!(by_score(&db), ["bob", "alice"]);
+}
+
+#[test]
+fn undeclared_index() {
+    let db = common::open();
+    assert!(matches!(
+        Score::index_scan("points", &[], Direction::Forward, &db),
+        Err(Error::IndexNotDeclared("points"))
+    ));
+}
+
+#[test]
+fn declared_index_is_built_on_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::build()
+        .add_collection::<UnindexedScore>()
+        .open(&path(&dir, "db"))
+        .unwrap();
+    UnindexedScore::put("alice", &UnindexedScore(2), &db).unwrap();
+    UnindexedScore::put("bob", &UnindexedScore(1), &db).unwrap();
+    drop(db);
+
+    let db = Database::build()
+        .add_collection::<Score>()
+        .open(&path(&dir, "db"))
+        .unwrap();
+    assert_eq!(by_score(&db), ["bob", "alice"]);
+    drop(db);
+
+    // Records deleted while the index isn't declared leave no entries once it's declared again.
+    let db = Database::build()
+        .add_collection::<UnindexedScore>()
+        .open(&path(&dir, "db"))
+        .unwrap();
+    UnindexedScore::delete("bob", &db).unwrap();
+    drop(db);
+    let db = Database::build()
+        .add_collection::<Score>()
+        .open(&path(&dir, "db"))
+        .unwrap();
+    assert_eq!(by_score(&db), ["alice"]);
+}
@@ -0,0 +1,99 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rkyvdb::{Collection, Database, Key, OwnedKey};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Game;
+
+impl Collection for Game {
+    type KeyType = (String, i64);
+    const CF_NAME: &'static str = "games";
+}
+
+fn assert_ordered<K: OwnedKey + std::fmt::Debug + PartialEq>(keys: &[K]) {
+    for pair in keys.windows(2) {
+        assert!(
+            pair[0].serialize() < pair[1].serialize(),
+            "{:?} should sort before {:?}",
+            pair[0],
+            pair[1]
+        );
+    }
+    for key in keys {
+        assert_eq!(&K::deserialize(&key.serialize()).unwrap(), key);
+    }
+}
+
+#[test]
+fn integers_are_ordered() {
+    assert_ordered(&[0u32, 1, 255, 256, u32::MAX]);
+    assert_ordered(&[i64::MIN, -256, -1, 0, 1, 256, i64::MAX]);
+}
+
+#[test]
+fn timestamps_are_ordered() {
+    assert_ordered(&[
+        UNIX_EPOCH - Duration::from_millis(1500),
+        UNIX_EPOCH - Duration::from_secs(1),
+        UNIX_EPOCH,
+        UNIX_EPOCH + Duration::from_nanos(1),
+        SystemTime::now(),
+    ]);
+}
+
+#[test]
+fn tuples_are_ordered_field_by_field() {
+    assert_ordered(&[
+        ("a".to_string(), 2u32),
+        ("a".to_string(), 10),
+        ("a\0".to_string(), 0),
+        ("ab".to_string(), 0),
+        ("b".to_string(), 0),
+    ]);
+}
+
+#[test]
+fn strings_end_before_fields_starting_with_0xff() {
+    assert_ordered(&[
+        ("a".to_string(), 0u8),
+        ("a".to_string(), 255),
+        ("a\0".to_string(), 0),
+        ("a\0".to_string(), 255),
+    ]);
+    assert_ordered(&[
+        ("x".to_string(), i32::MIN),
+        ("x".to_string(), i32::MAX),
+        ("x\0".to_string(), i32::MIN),
+    ]);
+    assert_ordered(&[
+        ("a".to_string(), 0xff000000u32),
+        ("a".to_string(), u32::MAX),
+        ("a\0".to_string(), 0),
+    ]);
+    assert_ordered(&[("a".to_string(), i64::MAX), ("a\0".to_string(), i64::MIN)]);
+}
+
+#[test]
+fn prefix_iter_by_first_field() {
+    let db = Database::build()
+        .add_collection::<Game>()
+        .open_in_memory()
+        .unwrap();
+    for (month, game) in [
+        ("2023-01", 5),
+        ("2023-01", -3),
+        ("2023-02", 1),
+        ("2022-12", 7),
+    ] {
+        Game::put((month.to_string(), game), &Game, &db).unwrap();
+    }
+    let games: Vec<_> = Game::prefix_iter_by(&("2023-01".to_string(),), &db)
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect();
+    assert_eq!(
+        games,
+        [("2023-01".to_string(), -3), ("2023-01".to_string(), 5)]
+    );
+}
@@ -0,0 +1,101 @@
+use rkyvdb::{Collection, Direction, Error, Store};
+
+mod common;
+
+use common::{keys, open, Score};
+
+#[test]
+fn put_get_delete() {
+    let db = open();
+    Score::put("Alice", &Score(3), &db).unwrap();
+    assert_eq!(Score::get("alice", &db).unwrap(), Some(Score(3)));
+    Score::modify("alice", &db, |score| score.map(|score| Score(score.0 + 1))).unwrap();
+    assert_eq!(Score::get("alice", &db).unwrap(), Some(Score(4)));
+    Score::delete("ALICE", &db).unwrap();
+    assert_eq!(Score::get("alice", &db).unwrap(), None);
+}
+
+#[test]
+fn iteration_order() {
+    let db = open();
+    for key in ["bob", "alice", "bobby", "carol"] {
+        Score::put(key, &Score(0), &db).unwrap();
+    }
+    assert_eq!(
+        keys(Score::iter(&db).unwrap()),
+        ["alice", "bob", "bobby", "carol"]
+    );
+    assert_eq!(
+        keys(Score::iter_rev(&db).unwrap()),
+        ["carol", "bobby", "bob", "alice"]
+    );
+    assert_eq!(
+        keys(Score::iter_from("b", Direction::Forward, &db).unwrap()),
+        ["bob", "bobby", "carol"]
+    );
+    assert_eq!(
+        keys(Score::iter_from("bz", Direction::Reverse, &db).unwrap()),
+        ["bobby", "bob", "alice"]
+    );
+    assert_eq!(
+        keys(Score::prefix_iter("bob", &db).unwrap()),
+        ["bob", "bobby"]
+    );
+}
+
+#[test]
+fn index_scan() {
+    let db = open();
+    Score::put("alice", &Score(2), &db).unwrap();
+    Score::put("bob", &Score(1), &db).unwrap();
+    Score::put("carol", &Score(3), &db).unwrap();
+    Score::put("alice", &Score(4), &db).unwrap();
+    assert_eq!(
+        keys(Score::index_scan("score", &[], Direction::Forward, &db).unwrap()),
+        ["bob", "carol", "alice"]
+    );
+    assert_eq!(
+        keys(Score::index_scan("score", &[], Direction::Reverse, &db).unwrap()),
+        ["alice", "carol", "bob"]
+    );
+    assert_eq!(
+        keys(Score::index_scan("score", &3u32.to_be_bytes(), Direction::Forward, &db).unwrap()),
+        ["carol"]
+    );
+}
+
+#[test]
+fn batch_is_applied_on_commit() {
+    let db = open();
+    let mut batch = db.batch();
+    Score::put("alice", &Score(1), &batch).unwrap();
+    assert_eq!(Score::get("alice", &batch).unwrap(), Some(Score(1)));
+    assert_eq!(Score::get("alice", &db).unwrap(), None);
+    batch.commit().unwrap();
+    assert_eq!(Score::get("alice", &db).unwrap(), Some(Score(1)));
+}
+
+#[test]
+fn multi_get_sees_pending_writes() {
+    let db = open();
+    Score::put("alice", &Score(1), &db).unwrap();
+    let batch = db.batch();
+    Score::put("bob", &Score(2), &batch).unwrap();
+    assert_eq!(
+        Score::multi_get(["bob", "carol", "alice"], &batch).unwrap(),
+        [Some(Score(2)), None, Some(Score(1))]
+    );
+    assert_eq!(
+        Score::multi_get(["bob", "alice"], &db).unwrap(),
+        [None, Some(Score(1))]
+    );
+}
+
+#[test]
+fn unregistered_collection() {
+    let db = open();
+    assert!(matches!(
+        db.write("missing", b"key", None),
+        Err(Error::CollectionNotRegistered)
+    ));
+}
@@ -0,0 +1,271 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use rkyvdb::{Collection, Database, DatabaseBuilder, Direction, Error};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct CounterV0(u64);
+
+impl Collection for CounterV0 {
+    type KeyType = u64;
+    const CF_NAME: &'static str = "counters";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct CounterV1 {
+    count: u64,
+}
+
+impl Collection for CounterV1 {
+    type KeyType = u64;
+    const CF_NAME: &'static str = "counters";
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct CounterV2 {
+    count: u64,
+    doubled: u64,
+}
+
+impl Collection for CounterV2 {
+    type KeyType = u64;
+    const CF_NAME: &'static str = "counters";
+    const SCHEMA_VERSION: u32 = 2;
+}
+
+/// Counter indexed by its value, stored like `CounterV0` in both versions.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct IndexedV0(u64);
+
+impl Collection for IndexedV0 {
+    type KeyType = u64;
+    const CF_NAME: &'static str = "counters";
+    const INDEXES: &'static [&'static str] = &["count"];
+
+    fn index_values(&self, _index: &str) -> Vec<Vec<u8>> {
+        vec![self.0.to_be_bytes().to_vec()]
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct IndexedV1(u64);
+
+impl Collection for IndexedV1 {
+    type KeyType = u64;
+    const CF_NAME: &'static str = "counters";
+    const SCHEMA_VERSION: u32 = 1;
+    const INDEXES: &'static [&'static str] = &["count"];
+
+    fn index_values(&self, _index: &str) -> Vec<Vec<u8>> {
+        vec![self.0.to_be_bytes().to_vec()]
+    }
+}
+
+fn v0_to_v1(old: CounterV0) -> Option<CounterV1> {
+    Some(CounterV1 { count: old.0 })
+}
+
+fn v1_to_v2(old: CounterV1) -> Option<CounterV2> {
+    Some(CounterV2 {
+        count: old.count,
+        doubled: old.count * 2,
+    })
+}
+
+fn v2_builder() -> DatabaseBuilder {
+    Database::build()
+        .add_collection::<CounterV2>()
+        .add_migration(0, v0_to_v1)
+        .add_migration(1, v1_to_v2)
+}
+
+/// Creates a DB at schema version 0 with counters `0..count`, each keyed by its value.
+fn create_v0(dir: &tempfile::TempDir, count: u64) -> String {
+    let path = common::path(dir, "db");
+    let db = Database::build()
+        .add_collection::<CounterV0>()
+        .open(&path)
+        .unwrap();
+    let mut batch = db.batch();
+    for value in 0..count {
+        CounterV0::put(value, &CounterV0(value), &batch).unwrap();
+    }
+    batch.commit().unwrap();
+    path
+}
+
+#[test]
+fn chained_migration() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = create_v0(&dir, 3);
+    let db = v2_builder().open(&path).unwrap();
+    assert_eq!(
+        CounterV2::get(2u64, &db).unwrap(),
+        Some(CounterV2 {
+            count: 2,
+            doubled: 4
+        })
+    );
+    assert_eq!(CounterV2::iter(&db).unwrap().count(), 3);
+}
+
+#[test]
+fn migration_deletes_records_mapped_to_none() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = create_v0(&dir, 4);
+    let db = Database::build()
+        .add_collection::<CounterV1>()
+        .add_migration(0, |old: CounterV0| {
+            (old.0 % 2 == 0).then_some(CounterV1 { count: old.0 })
+        })
+        .open(&path)
+        .unwrap();
+    let keys: Vec<u64> = CounterV1::iter(&db)
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect();
+    assert_eq!(keys, [0, 2]);
+}
+
+#[test]
+fn interrupted_migration_resumes() {
+    // More than one chunk of progress, see `migration::CHUNK_SIZE`.
+    const RECORDS: u64 = 25_000;
+    const FAIL_AT: u64 = 15_000;
+    let dir = tempfile::tempdir().unwrap();
+    let path = create_v0(&dir, RECORDS);
+    let interrupted = catch_unwind(AssertUnwindSafe(|| {
+        Database::build()
+            .add_collection::<CounterV1>()
+            .add_migration(0, |old: CounterV0| {
+                assert_ne!(old.0, FAIL_AT, "Interrupting migration");
+                v0_to_v1(old)
+            })
+            .open(&path)
+    }));
+    assert!(interrupted.is_err());
+
+    // Only records after the last saved chunk are left.
+    let reports = Database::build()
+        .add_collection::<CounterV1>()
+        .add_migration(0, v0_to_v1)
+        .dry_run(&path)
+        .unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].records, RECORDS - 10_000);
+
+    let db = Database::build()
+        .add_collection::<CounterV1>()
+        .add_migration(0, v0_to_v1)
+        .open(&path)
+        .unwrap();
+    for value in [0, 9_999, 10_000, FAIL_AT, RECORDS - 1] {
+        assert_eq!(
+            CounterV1::get(value, &db).unwrap(),
+            Some(CounterV1 { count: value })
+        );
+    }
+}
+
+#[test]
+fn resumed_migration_reindexes() {
+    // The first chunk, see `migration::CHUNK_SIZE`, is changed and saved before interrupting.
+    // The resumed part has nothing left to change.
+    const RECORDS: u64 = 10_001;
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::path(&dir, "db");
+    let db = Database::build()
+        .add_collection::<IndexedV0>()
+        .open(&path)
+        .unwrap();
+    let mut batch = db.batch();
+    for value in 0..RECORDS {
+        IndexedV0::put(value, &IndexedV0(value), &batch).unwrap();
+    }
+    batch.commit().unwrap();
+    drop(db);
+    let builder = |interrupt: bool| {
+        Database::build()
+            .add_collection::<IndexedV1>()
+            .add_migration(0, move |old: IndexedV0| {
+                assert!(!interrupt || old.0 < 10_000, "Interrupting migration");
+                Some(IndexedV1(if old.0 < 10_000 { old.0 + 1 } else { old.0 }))
+            })
+    };
+    let interrupted = catch_unwind(AssertUnwindSafe(|| builder(true).open(&path)));
+    assert!(interrupted.is_err());
+
+    let db = builder(false).open(&path).unwrap();
+    let found = |count: u64| -> Vec<u64> {
+        IndexedV1::index_scan("count", &count.to_be_bytes(), Direction::Forward, &db)
+            .unwrap()
+            .map(|entry| entry.unwrap().0)
+            .collect()
+    };
+    assert!(found(0).is_empty());
+    assert_eq!(found(1), [0]);
+    assert_eq!(found(10_000), [9_999, 10_000]);
+}
+
+#[test]
+fn missing_migration() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = create_v0(&dir, 1);
+    let result = Database::build()
+        .add_collection::<CounterV2>()
+        .add_migration(1, v1_to_v2)
+        .open(&path);
+    assert!(matches!(
+        result,
+        Err(Error::MissingMigration {
+            collection: "counters",
+            version: 0,
+        })
+    ));
+}
+
+#[test]
+fn schema_too_new() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = create_v0(&dir, 1);
+    drop(v2_builder().open(&path).unwrap());
+    let result = Database::build()
+        .add_collection::<CounterV1>()
+        .add_migration(0, v0_to_v1)
+        .open(&path);
+    assert!(matches!(
+        result,
+        Err(Error::SchemaTooNew {
+            collection: "counters",
+            stored: 2,
+            supported: 1,
+        })
+    ));
+}
+
+#[test]
+fn dry_run_leaves_db_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = create_v0(&dir, 3);
+    let reports = v2_builder().dry_run(&path).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(
+        (
+            reports[0].from_version,
+            reports[0].to_version,
+            reports[0].records,
+            reports[0].changed
+        ),
+        (0, 2, 3, 3)
+    );
+
+    // Read-only opens check that the stored version is still 0.
+    let db = Database::build()
+        .add_collection::<CounterV0>()
+        .open_read_only(&path)
+        .unwrap();
+    assert_eq!(CounterV0::get(1u64, &db).unwrap(), Some(CounterV0(1)));
+}
@@ -0,0 +1,70 @@
+use rkyvdb::{CaseInsensitiveString, Collection, Database, Error};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::{path, Counter};
+
+#[test]
+fn read_only_rejects_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let primary = Database::build()
+        .add_collection::<Counter>()
+        .open(&path(&dir, "db"))
+        .unwrap();
+    Counter::put("key", &Counter(1), &primary).unwrap();
+
+    let read_only = Database::build()
+        .add_collection::<Counter>()
+        .open_read_only(&path(&dir, "db"))
+        .unwrap();
+    assert_eq!(Counter::get("key", &read_only).unwrap(), Some(Counter(1)));
+    assert!(matches!(
+        Counter::put("key", &Counter(2), &read_only),
+        Err(Error::ReadOnly)
+    ));
+}
+
+#[test]
+fn secondary_catches_up_with_primary() {
+    let dir = tempfile::tempdir().unwrap();
+    let primary = Database::build()
+        .add_collection::<Counter>()
+        .open(&path(&dir, "db"))
+        .unwrap();
+    Counter::put("key", &Counter(1), &primary).unwrap();
+
+    let secondary = Database::build()
+        .add_collection::<Counter>()
+        .open_secondary(&path(&dir, "db"), &path(&dir, "secondary"))
+        .unwrap();
+    assert_eq!(Counter::get("key", &secondary).unwrap(), Some(Counter(1)));
+
+    Counter::put("key", &Counter(2), &primary).unwrap();
+    secondary.catch_up_with_primary().unwrap();
+    assert_eq!(Counter::get("key", &secondary).unwrap(), Some(Counter(2)));
+}
+
+#[derive(Serialize, Deserialize)]
+struct NewerCounter(u64);
+
+impl Collection for NewerCounter {
+    type KeyType = CaseInsensitiveString;
+    const CF_NAME: &'static str = "counters";
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+#[test]
+fn read_only_does_not_migrate() {
+    let dir = tempfile::tempdir().unwrap();
+    drop(
+        Database::build()
+            .add_collection::<Counter>()
+            .open(&path(&dir, "db"))
+            .unwrap(),
+    );
+    let result = Database::build()
+        .add_collection::<NewerCounter>()
+        .open_read_only(&path(&dir, "db"));
+    assert!(matches!(result, Err(Error::NotUpToDate("counters"))));
+}
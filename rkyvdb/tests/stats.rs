@@ -0,0 +1,32 @@
+use rkyvdb::{Collection, Error};
+
+mod common;
+
+use common::{path, Counter};
+
+#[test]
+fn collection_stats_after_compaction() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::builder().open(&path(&dir, "db")).unwrap();
+    assert!(db.cf_names().contains(&"counters"));
+    for index in 0..100 {
+        Counter::put(format!("key{index}").as_str(), &Counter(index), &db).unwrap();
+    }
+    db.compact("counters").unwrap();
+    let stats = db.collection_stats("counters").unwrap();
+    assert_eq!(stats.estimated_keys, 100);
+    assert!(stats.sst_size > 0);
+    assert_eq!(db.collection_stats("scores").unwrap().sst_size, 0);
+    assert!(matches!(
+        db.collection_stats("missing"),
+        Err(Error::CollectionNotRegistered)
+    ));
+}
+
+#[test]
+fn in_memory_stats_count_keys() {
+    let db = common::open();
+    Counter::put("alice", &Counter(1), &db).unwrap();
+    Counter::put("bob", &Counter(2), &db).unwrap();
+    assert_eq!(db.collection_stats("counters").unwrap().estimated_keys, 2);
+}
@@ -0,0 +1,124 @@
+use std::fs;
+
+use rkyvdb::{Collection, Database, Direction, Tuning};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Move(u32);
+
+impl Collection for Move {
+    /// Game id and move number.
+    type KeyType = (u64, u32);
+    const CF_NAME: &'static str = "moves";
+    const TUNING: Tuning = Tuning {
+        bloom_filter_bits: Some(10.0),
+        block_cache_size: Some(1 << 20),
+        prefix_len: Some(8),
+        compression_level: Some(3),
+    };
+}
+
+fn keys(iter: impl Iterator<Item = Result<((u64, u32), Move), rkyvdb::Error>>) -> Vec<(u64, u32)> {
+    iter.map(|entry| entry.unwrap().0).collect()
+}
+
+#[test]
+fn tuned_collection_scans_see_all_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::build()
+        .add_collection::<Move>()
+        .open(&common::path(&dir, "db"))
+        .unwrap();
+    for game in [1, 2, 3] {
+        for number in [1, 2] {
+            Move::put((game, number), &Move(number), &db).unwrap();
+        }
+    }
+    db.compact("moves").unwrap();
+
+    assert_eq!(Move::get((2, 2), &db).unwrap(), Some(Move(2)));
+    assert_eq!(Move::get((4, 1), &db).unwrap(), None);
+    // Covered by the prefix extractor.
+    assert_eq!(
+        keys(Move::prefix_iter_by(&(2u64,), &db).unwrap()),
+        [(2, 1), (2, 2)]
+    );
+    // Shorter than the extracted prefix, or none at all.
+    assert_eq!(keys(Move::iter(&db).unwrap()).len(), 6);
+    assert_eq!(
+        keys(Move::iter_from((2, 2), Direction::Forward, &db).unwrap()),
+        [(2, 2), (3, 1), (3, 2)]
+    );
+    assert_eq!(keys(Move::iter_rev(&db).unwrap()).first(), Some(&(3, 2)));
+}
+
+/// Options of the column family RocksDB persisted in the newest `OPTIONS-*` file of the DB,
+/// including its table options.
+fn persisted_options(path: &str, cf_name: &str) -> String {
+    let options_file = fs::read_dir(path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter_map(|path| {
+            let number = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix("OPTIONS-")?
+                .parse::<u64>()
+                .ok()?;
+            Some((number, path))
+        })
+        .max()
+        .unwrap()
+        .1;
+    let options = fs::read_to_string(options_file).unwrap();
+    let start = options.find(&format!("[CFOptions \"{cf_name}\"]")).unwrap();
+    let section = &options[start..];
+    let end = section[1..]
+        .find("[CFOptions ")
+        .map_or(section.len(), |end| end + 1);
+    section[..end].to_string()
+}
+
+fn option<'a>(options: &'a str, name: &str) -> Option<&'a str> {
+    options
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+#[test]
+fn tuning_reaches_column_family() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::path(&dir, "db");
+    let db = common::builder()
+        .add_collection::<Move>()
+        .open(&path)
+        .unwrap();
+    drop(db);
+
+    let moves = persisted_options(&path, "moves");
+    assert_eq!(option(&moves, "compression"), Some("kZSTD"), "{moves}");
+    assert_eq!(
+        option(&moves, "prefix_extractor"),
+        Some("rocksdb.FixedPrefix.8"),
+        "{moves}"
+    );
+    let filter_policy = option(&moves, "filter_policy").unwrap_or_default();
+    assert!(filter_policy.starts_with("bloomfilter:10"), "{moves}");
+
+    // Untuned collections keep RocksDB's defaults.
+    let counters = persisted_options(&path, "counters");
+    assert_ne!(
+        option(&counters, "compression"),
+        Some("kZSTD"),
+        "{counters}"
+    );
+    assert_ne!(
+        option(&counters, "prefix_extractor"),
+        Some("rocksdb.FixedPrefix.8"),
+        "{counters}"
+    );
+    let filter_policy = option(&counters, "filter_policy").unwrap_or_default();
+    assert!(!filter_policy.starts_with("bloomfilter"), "{counters}");
+}
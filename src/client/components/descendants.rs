@@ -0,0 +1,66 @@
+use dioxus::prelude::*;
+use reqwest::StatusCode;
+
+use crate::{
+    client::{components::WCN, uno::UnoAttributes},
+    data::DescendantsInfo,
+    util::Root,
+};
+
+#[inline_props]
+pub fn Descendants<'a>(cx: Scope<'a>, root: &'static Root, id: &'a str) -> Element {
+    let id = id.to_string();
+    let descendants = {
+        use_future(&cx, (&id, &root.name), |(id, root)| async move {
+            let resp = reqwest::get(format!("https://freopen.org/api/{root}/descendants/{id}"))
+                .await
+                .unwrap();
+            if resp.status() == StatusCode::NOT_FOUND {
+                None
+            } else {
+                assert!(resp.status().is_success());
+                Some(
+                    rmp_serde::decode::from_slice::<DescendantsInfo>(&resp.bytes().await.unwrap())
+                        .unwrap(),
+                )
+            }
+        })
+    };
+
+    let descendants = match descendants.value() {
+        Some(Some(descendants)) if !descendants.children.is_empty() => descendants,
+        _ => return None,
+    };
+    let root_name = root.name;
+    cx.render(rsx!(
+        div {
+            u_m: "4",
+            h2 {
+                u_text: "2xl",
+                u_m: "b-2",
+                "{descendants.subtree_size} players got their " WCN{} " through {descendants.id}"
+            }
+            ul {
+                descendants.children.iter().map(|child| rsx!(
+                    li {
+                        key: "{child.id}",
+                        Link {
+                            to: "/{root_name}/@/{child.id}",
+                            span {
+                                u_font: "bold",
+                                u_bg: "hover:sky-300",
+                                u_border: "rounded",
+                                u_p: "1",
+                                "{child.id}"
+                            }
+                        }
+                        span {
+                            u_p: "1",
+                            "+{child.subtree_size}"
+                        }
+                    }
+                ))
+            }
+        }
+    ))
+}
@@ -7,11 +7,13 @@ use crate::{
         uno::UnoAttributes,
     },
     data::{ErdosLink, PlayerInfo, Termination, TimeControl, TimeControlType},
+    util::Root,
 };
 
 #[inline_props]
 pub fn ErdosChainList<'a>(
     cx: Scope<'a>,
+    root: &'static Root,
     id: &'a str,
     chain: &'a Vec<ErdosLink>,
     to: Option<&'a DateTime<Utc>>,
@@ -54,6 +56,7 @@ pub fn ErdosChainList<'a>(
                 rsx!(
                     ErdosLinkCard {
                         key: "{key}",
+                        root: root,
                         winner: winner,
                         link: link,
                     }
@@ -64,7 +67,12 @@ pub fn ErdosChainList<'a>(
 }
 
 #[inline_props]
-fn ErdosLinkCard<'a>(cx: Scope<'a>, winner: &'a str, link: &'a ErdosLink) -> Element {
+fn ErdosLinkCard<'a>(
+    cx: Scope<'a>,
+    root: &'static Root,
+    winner: &'a str,
+    link: &'a ErdosLink,
+) -> Element {
     let winner_color = if link.winner_is_white {
         "i-fa-regular:circle"
     } else {
@@ -102,6 +110,7 @@ fn ErdosLinkCard<'a>(cx: Scope<'a>, winner: &'a str, link: &'a ErdosLink) -> Ele
                         class: "{winner_color}",
                     }
                     PlayerLabel {
+                        root: root,
                         id: winner,
                         info: &link.winner_info,
                         erdos: link.erdos_number,
@@ -113,6 +122,7 @@ fn ErdosLinkCard<'a>(cx: Scope<'a>, winner: &'a str, link: &'a ErdosLink) -> Ele
                         class: "{loser_color}",
                     }
                     PlayerLabel {
+                        root: root,
                         id: &link.loser_id,
                         info: &link.loser_info,
                         erdos: link.erdos_number - 1,
@@ -124,7 +134,13 @@ fn ErdosLinkCard<'a>(cx: Scope<'a>, winner: &'a str, link: &'a ErdosLink) -> Ele
 }
 
 #[inline_props]
-fn PlayerLabel<'a>(cx: Scope<'a>, id: &'a str, info: &'a PlayerInfo, erdos: u32) -> Element {
+fn PlayerLabel<'a>(
+    cx: Scope<'a>,
+    root: &'static Root,
+    id: &'a str,
+    info: &'a PlayerInfo,
+    erdos: u32,
+) -> Element {
     let title = if info.title.is_empty() {
         None
     } else {
@@ -138,9 +154,10 @@ fn PlayerLabel<'a>(cx: Scope<'a>, id: &'a str, info: &'a PlayerInfo, erdos: u32)
         ))
     };
     let rating_change = format!("{:+}", info.rating_change);
+    let root_name = root.name;
     cx.render(rsx!(
         Link {
-            to: "/@/{id}",
+            to: "/{root_name}/@/{id}",
             span {
                 u_p: "0.5",
                 u_text: "xs fuchsia-900",
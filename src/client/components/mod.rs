@@ -1,9 +1,12 @@
 #![allow(non_snake_case)]
+mod descendants;
 mod erdos_chain_list;
+mod root_selector;
 mod time;
 mod wcn;
 
+pub use descendants::Descendants;
 pub use erdos_chain_list::ErdosChainList;
+pub use root_selector::RootSelector;
 pub use time::Time;
-pub use time::WC_TIME;
 pub use wcn::WCN;
@@ -0,0 +1,41 @@
+use dioxus::prelude::*;
+
+use crate::{
+    client::uno::UnoAttributes,
+    util::{Root, ROOTS},
+};
+
+/// Links to the same page of other roots, i.e. `/{root}{suffix}`. Hidden with a single root.
+#[inline_props]
+pub fn RootSelector(cx: Scope, current: &'static Root, suffix: String) -> Element {
+    if ROOTS.len() < 2 {
+        return None;
+    }
+    cx.render(rsx!(
+        div {
+            u_m: "b-4",
+            ROOTS.iter().map(|root| {
+                let name = root.name;
+                if root == *current {
+                    rsx!(span {
+                        key: "{name}",
+                        u_p: "2",
+                        u_font: "bold",
+                        "{name}"
+                    })
+                } else {
+                    rsx!(Link {
+                        key: "{name}",
+                        to: "/{name}{suffix}",
+                        span {
+                            u_p: "2",
+                            u_text: "sky-600",
+                            u_underline: "~",
+                            "{name}"
+                        }
+                    })
+                }
+            })
+        }
+    ))
+}
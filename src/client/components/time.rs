@@ -1,4 +1,4 @@
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use dioxus::prelude::*;
 use lazy_static::lazy_static;
 
@@ -6,7 +6,6 @@ lazy_static! {
     pub static ref LOCAL_TZ: chrono::FixedOffset =
         chrono::FixedOffset::west_opt((js_sys::Date::new_0().get_timezone_offset() * 60.) as i32)
             .unwrap();
-    pub static ref WC_TIME: DateTime<Utc> = Utc.with_ymd_and_hms(2013, 11, 22, 0, 0, 0).unwrap();
 }
 
 #[inline_props]
@@ -3,14 +3,17 @@ use reqwest::StatusCode;
 
 use crate::{
     client::{
-        components::{ErdosChainList, Time, WCN, WC_TIME},
+        components::{Descendants, ErdosChainList, RootSelector, Time, WCN},
         uno::UnoAttributes,
     },
     data::ErdosChains,
-    util::ERDOS_ID,
+    util::{Root, DEFAULT_ROOT},
 };
 
-fn WCErdosChains(cx: Scope) -> Element {
+#[inline_props]
+fn RootErdosChains(cx: Scope, root: &'static Root) -> Element {
+    let start = &*cx.use_hook(|_| root.event_date());
+    let (event_title, event_link) = root.event;
     cx.render(rsx!(
         div {
             class: "w-max-content",
@@ -24,7 +27,7 @@ fn WCErdosChains(cx: Scope) -> Element {
                 u_text: "center",
                 "from: "
                 Time {
-                    time: &WC_TIME,
+                    time: start,
                 }
             }
             div {
@@ -33,10 +36,10 @@ fn WCErdosChains(cx: Scope) -> Element {
                 "to: now"
             }
             a {
-                href: "https://en.wikipedia.org/wiki/World_Chess_Championship_2013",
+                href: "{event_link}",
                 u_text: "sky-600",
                 u_underline: "~",
-                "WCC 2013"
+                "{event_title}"
             }
         }
     ))
@@ -45,13 +48,20 @@ fn WCErdosChains(cx: Scope) -> Element {
 pub fn ErdosChains(cx: Scope) -> Element {
     let route = use_route(&cx);
     let id = route.segment("id").unwrap().to_string();
-    if id.to_lowercase() == ERDOS_ID.to_lowercase() {
-        return cx.render(rsx!(WCErdosChains {}));
+    let root = route
+        .segment("root")
+        .and_then(Root::find)
+        .unwrap_or(DEFAULT_ROOT);
+    if id.to_lowercase() == root.id.to_lowercase() {
+        return cx.render(rsx!(
+            RootSelector { current: root, suffix: format!("/@/{}", root.id) }
+            RootErdosChains { root: root }
+        ));
     }
 
     let erdos_chains = {
-        use_future(&cx, (&id,), |(id,)| async move {
-            let resp = reqwest::get(format!("https://freopen.org/api/erdos_chains/{id}"))
+        use_future(&cx, (&id, &root.name), |(id, root)| async move {
+            let resp = reqwest::get(format!("https://freopen.org/api/{root}/erdos_chains/{id}"))
                 .await
                 .unwrap();
             if resp.status() == StatusCode::NOT_FOUND {
@@ -66,7 +76,7 @@ pub fn ErdosChains(cx: Scope) -> Element {
         })
     };
 
-    cx.render(if let Some(erdos_chains) = erdos_chains.value() {
+    let chains = if let Some(erdos_chains) = erdos_chains.value() {
         if let Some(erdos_chains) = erdos_chains {
             if erdos_chains.erdos_chains.is_empty() {
                 rsx! (
@@ -87,6 +97,7 @@ pub fn ErdosChains(cx: Scope) -> Element {
                                 rsx!(
                                     ErdosChainList {
                                         key: "{key}",
+                                        root: root,
                                         id: &erdos_chains.id,
                                         chain: chain,
                                         to: prev_to,
@@ -96,6 +107,7 @@ pub fn ErdosChains(cx: Scope) -> Element {
                                 rsx!(
                                     ErdosChainList {
                                         key: "{key}",
+                                        root: root,
                                         id: &erdos_chains.id,
                                         chain: chain,
                                     }
@@ -118,5 +130,17 @@ pub fn ErdosChains(cx: Scope) -> Element {
                 "Loading..."
             }
         )
-    })
+    };
+
+    cx.render(rsx!(
+        RootSelector {
+            current: root,
+            suffix: format!("/@/{id}"),
+        }
+        chains
+        Descendants {
+            root: root,
+            id: route.segment("id").unwrap(),
+        }
+    ))
 }
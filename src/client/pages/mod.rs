@@ -4,6 +4,8 @@ use dioxus::prelude::*;
 mod erdos_chains;
 mod home;
 mod layout;
+mod stats;
+mod winning_path;
 
 pub fn app(cx: Scope) -> Element {
     cx.render(rsx! {
@@ -17,6 +19,22 @@ pub fn app(cx: Scope) -> Element {
                     to: "/@/:id",
                     erdos_chains::ErdosChains {}
                 }
+                Route {
+                    to: "/:root/@/:id",
+                    erdos_chains::ErdosChains {}
+                }
+                Route {
+                    to: "/path/:from/:to",
+                    winning_path::WinningPath {}
+                }
+                Route {
+                    to: "/stats",
+                    stats::Stats {}
+                }
+                Route {
+                    to: "/:root/stats",
+                    stats::Stats {}
+                }
                 Redirect {
                     from: ""
                     to: "/"
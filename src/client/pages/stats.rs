@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use dioxus::prelude::*;
+
+use crate::{
+    client::{
+        components::{RootSelector, WCN},
+        uno::UnoAttributes,
+    },
+    data::Statistics,
+    util::{Root, DEFAULT_ROOT},
+};
+
+#[inline_props]
+fn Bar(cx: Scope, label: String, count: u64, max: u64) -> Element {
+    let width = *count as f64 * 100. / (*max).max(1) as f64;
+    cx.render(rsx!(
+        div {
+            u_flex: "~ nowrap",
+            u_m: "b-1",
+            span {
+                u_w: "20",
+                u_font: "bold",
+                "{label}"
+            }
+            div {
+                u_w: "md",
+                div {
+                    u_bg: "sky-300",
+                    u_h: "4",
+                    u_border: "rounded",
+                    style: "width: {width}%",
+                }
+            }
+            span {
+                u_p: "l-2",
+                "{count}"
+            }
+        }
+    ))
+}
+
+pub fn Stats(cx: Scope) -> Element {
+    let route = use_route(&cx);
+    let root = route
+        .segment("root")
+        .and_then(Root::find)
+        .unwrap_or(DEFAULT_ROOT);
+    let statistics = use_future(&cx, (), |_| async move {
+        let resp = reqwest::get("https://freopen.org/api/stats").await.unwrap();
+        assert!(resp.status().is_success());
+        rmp_serde::decode::from_slice::<Statistics>(&resp.bytes().await.unwrap()).unwrap()
+    });
+    let statistics = match statistics.value() {
+        Some(statistics) => statistics,
+        None => {
+            return cx.render(rsx!(
+                div {
+                    "Loading..."
+                }
+            ))
+        }
+    };
+    let root_statistics = statistics.roots.get(root.name).cloned().unwrap_or_default();
+    let total = |counts: &BTreeMap<u32, u64>| counts.values().sum::<u64>();
+    let max_count = root_statistics.current.values().copied().max().unwrap_or(0);
+    let max_total = root_statistics
+        .months
+        .values()
+        .map(total)
+        .max()
+        .unwrap_or(0);
+    let current_total = total(&root_statistics.current);
+    cx.render(rsx!(
+        div {
+            RootSelector {
+                current: root,
+                suffix: "/stats".to_string(),
+            }
+            h1 {
+                u_text: "2xl",
+                u_m: "b-4",
+                "{current_total} players have " WCN{} " of {root.name}"
+            }
+            root_statistics.current.iter().map(|(erdos_number, count)| rsx!(
+                Bar {
+                    key: "{erdos_number}",
+                    label: erdos_number.to_string(),
+                    count: *count,
+                    max: max_count,
+                }
+            ))
+            h2 {
+                u_text: "2xl",
+                u_m: "y-4",
+                "Players with " WCN{} " over time"
+            }
+            root_statistics.months.iter().map(|(month, counts)| rsx!(
+                Bar {
+                    key: "{month}",
+                    label: month.clone(),
+                    count: total(counts),
+                    max: max_total,
+                }
+            ))
+        }
+    ))
+}
@@ -0,0 +1,107 @@
+use dioxus::prelude::*;
+use reqwest::StatusCode;
+
+use crate::{
+    client::{components::Time, uno::UnoAttributes},
+    data::{Win, WinningPath},
+};
+
+#[inline_props]
+fn WinCard<'a>(cx: Scope<'a>, winner: &'a str, win: &'a Win) -> Element {
+    cx.render(rsx!(
+        a {
+            href: "https://lichess.org/{win.game_id}",
+            div {
+                u_p: "1",
+                u_bg: "hover:sky-100",
+                u_border: "rounded",
+                u_transition: "~ all duration-300",
+                Time {
+                    time: &win.time,
+                }
+                span {
+                    u_p: "2",
+                    u_font: "bold",
+                    "{winner}"
+                }
+                "beat"
+                span {
+                    u_p: "2",
+                    u_font: "bold",
+                    "{win.loser_id}"
+                }
+            }
+        }
+    ))
+}
+
+pub fn WinningPath(cx: Scope) -> Element {
+    let route = use_route(&cx);
+    let from = route.segment("from").unwrap().to_string();
+    let to = route.segment("to").unwrap().to_string();
+
+    let winning_path = {
+        use_future(&cx, (&from, &to), |(from, to)| async move {
+            let resp = reqwest::get(format!("https://freopen.org/api/winning_path/{from}/{to}"))
+                .await
+                .unwrap();
+            // Not found when the server doesn't record wins.
+            if resp.status() == StatusCode::NOT_FOUND {
+                None
+            } else {
+                assert!(resp.status().is_success());
+                Some(
+                    rmp_serde::decode::from_slice::<WinningPath>(&resp.bytes().await.unwrap())
+                        .unwrap(),
+                )
+            }
+        })
+    };
+
+    cx.render(if let Some(winning_path) = winning_path.value() {
+        let winning_path = match winning_path {
+            Some(winning_path) => winning_path,
+            None => {
+                return cx.render(rsx!(
+                    div {
+                        "Winning paths are not available on this server."
+                    }
+                ))
+            }
+        };
+        if winning_path.path.is_empty() {
+            rsx! (
+                div {
+                    "No chain of wins from {winning_path.from} to {winning_path.to} found."
+                }
+            )
+        } else {
+            let mut winner: &str = &winning_path.from;
+            rsx! (
+                div {
+                    h1 {
+                        u_text: "2xl",
+                        u_m: "b-4",
+                        "How {winning_path.from} beat {winning_path.to}"
+                    }
+                    winning_path.path.iter().map(|win| {
+                        let winner = std::mem::replace(&mut winner, &win.loser_id);
+                        rsx!(
+                            WinCard {
+                                key: "{win.game_id}",
+                                winner: winner,
+                                win: win,
+                            }
+                        )
+                    })
+                }
+            )
+        }
+    } else {
+        rsx! (
+            div {
+                "Loading..."
+            }
+        )
+    })
+}
@@ -1,13 +1,79 @@
-use rkyvdb::{CaseInsensitiveString, Collection};
+use rkyvdb::{CaseInsensitiveString, Collection, Tuning};
 
-use super::{ServerMetadata, User};
+use super::{Child, ServerMetadata, Statistics, SubtreeSize, User, Win};
 
 impl Collection for User {
     type KeyType = CaseInsensitiveString;
     const CF_NAME: &'static str = "users";
+    const SCHEMA_VERSION: u32 = 1;
+    const INDEXES: &'static [&'static str] = &["erdos_number", "last_improvement"];
+    /// Most API requests look up a few users by name, often ones which don't exist.
+    const TUNING: Tuning = Tuning {
+        bloom_filter_bits: Some(10.0),
+        block_cache_size: Some(256 << 20),
+        ..Tuning::DEFAULT
+    };
+
+    /// Values are the root name and `\0`, followed by the big-endian number or timestamp of
+    /// the latest link to that root.
+    fn index_values(&self, index: &str) -> Vec<Vec<u8>> {
+        self.erdos_links
+            .iter()
+            .filter_map(|(root, erdos_links)| {
+                let erdos_link = erdos_links.last()?;
+                let value = match index {
+                    "erdos_number" => erdos_link.erdos_number.to_be_bytes().to_vec(),
+                    "last_improvement" => {
+                        (erdos_link.time.timestamp() as u64).to_be_bytes().to_vec()
+                    }
+                    _ => return None,
+                };
+                Some([root.as_bytes(), b"\0".as_slice(), value.as_slice()].concat())
+            })
+            .collect()
+    }
 }
 
 impl Collection for ServerMetadata {
     type KeyType = ();
     const CF_NAME: &'static str = "metadata";
 }
+
+/// One row per win, so that recording a win doesn't rewrite the winner's earlier ones. Keyed
+/// by winner, loser, Unix time and game id, so a winner's wins are ordered by loser, then time.
+impl Collection for Win {
+    type KeyType = (CaseInsensitiveString, CaseInsensitiveString, i64, String);
+    const CF_NAME: &'static str = "wins";
+}
+
+impl Win {
+    pub fn key(&self, winner_id: &str) -> <Win as Collection>::KeyType {
+        (
+            winner_id.into(),
+            self.loser_id.as_str().into(),
+            self.time.timestamp(),
+            self.game_id.clone(),
+        )
+    }
+}
+
+/// Keyed by root name, parent and child, so that a user's children are listed by prefix.
+impl Collection for Child {
+    type KeyType = (String, CaseInsensitiveString, CaseInsensitiveString);
+    const CF_NAME: &'static str = "children";
+}
+
+/// Keyed by root name and user.
+impl Collection for SubtreeSize {
+    type KeyType = (String, CaseInsensitiveString);
+    const CF_NAME: &'static str = "subtree_sizes";
+    const TUNING: Tuning = Tuning {
+        bloom_filter_bits: Some(10.0),
+        ..Tuning::DEFAULT
+    };
+}
+
+impl Collection for Statistics {
+    type KeyType = ();
+    const CF_NAME: &'static str = "statistics";
+}
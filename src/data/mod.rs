@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 #[cfg(unix)]
@@ -6,7 +8,8 @@ mod collections;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
-    pub erdos_links: Vec<ErdosLink>,
+    /// Links which improved the user's number, keyed by root name.
+    pub erdos_links: BTreeMap<String, Vec<ErdosLink>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -53,13 +56,86 @@ pub enum Termination {
     Time,
 }
 
+/// Eligible win of a player over an opponent, a hop of a `WinningPath`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Win {
+    pub loser_id: String,
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub game_id: String,
+}
+
+/// Chain of wins from `from` to `to`, each played before the previous one. Empty if there is
+/// none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WinningPath {
+    pub from: String,
+    pub to: String,
+    pub path: Vec<Win>,
+}
+
+/// User whose latest link to a root is a win over another one, a row of the reverse index of
+/// `User::erdos_links`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Child {
+    pub id: String,
+}
+
+/// Number of users below a user in a root's tree, following latest links.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubtreeSize(pub u64);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DescendantsInfo {
+    pub id: String,
+    pub subtree_size: u64,
+    pub children: Vec<ChildInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChildInfo {
+    pub id: String,
+    pub subtree_size: u64,
+}
+
+/// Erdos number distribution, keyed by root name.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Statistics {
+    pub roots: BTreeMap<String, RootStatistics>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RootStatistics {
+    /// Number of users per Erdos number.
+    pub current: BTreeMap<u32, u64>,
+    /// `current` as of the end of each processed archive, keyed by `YYYY-MM`.
+    pub months: BTreeMap<String, BTreeMap<u32, u64>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErdosChains {
     pub id: String,
     pub erdos_chains: Vec<Vec<ErdosLink>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ServerMetadata {
     pub last_processed_archive: String,
+    #[serde(default)]
+    pub checkpoint: Option<ArchiveCheckpoint>,
+    /// Whether every processed archive was processed with `--record-wins`, i.e. there is a
+    /// `Win` row for every eligible win.
+    #[serde(default)]
+    pub wins_recorded: bool,
+    /// Whether `Child` and `SubtreeSize` were built from `User`, see `descendants::build`.
+    #[serde(default)]
+    pub descendants_built: bool,
+    /// Whether `Statistics::current` was computed from `User`, see `statistics::build`.
+    #[serde(default)]
+    pub statistics_built: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveCheckpoint {
+    pub archive: String,
+    pub games_processed: u64,
 }
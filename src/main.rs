@@ -1,8 +1,10 @@
+use clap::Parser;
+
 mod data;
 mod server;
 mod util;
 
 #[tokio::main]
 async fn main() {
-    server::serve().await.unwrap();
+    server::serve(server::Args::parse()).await.unwrap();
 }
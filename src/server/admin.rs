@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::{extract::Path, http::StatusCode, routing::post, Extension, Router};
+use metrics::gauge;
+use tokio::{task::spawn_blocking, time::sleep};
+use tracing::{info, warn};
+
+use super::{
+    backup::{self, BackupOptions},
+    rebuild::SwappableDatabase,
+};
+
+const DB_METRICS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically exports RocksDB's estimates for every collection as gauges labeled with the
+/// collection name. Failures are logged and retried on the next tick, so the task never stops.
+pub async fn export_db_metrics_task(db: SwappableDatabase) -> Result<()> {
+    loop {
+        let stats = db
+            .get()
+            .run(|db| {
+                db.cf_names()
+                    .iter()
+                    .map(|&cf_name| Ok((cf_name, db.collection_stats(cf_name)?)))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await;
+        match stats {
+            Ok(stats) => {
+                for (cf_name, stats) in stats {
+                    gauge!("db_estimated_keys", stats.estimated_keys as f64, "collection" => cf_name);
+                    gauge!("db_sst_size_bytes", stats.sst_size as f64, "collection" => cf_name);
+                    gauge!(
+                        "db_pending_compaction_bytes",
+                        stats.pending_compaction_bytes as f64,
+                        "collection" => cf_name
+                    );
+                }
+            }
+            Err(err) => warn!("Failed to read DB metrics, retrying: {err:#}"),
+        }
+        sleep(DB_METRICS_INTERVAL).await;
+    }
+}
+
+async fn compact_handler(
+    Path(collection): Path<String>,
+    Extension(db): Extension<SwappableDatabase>,
+) -> (StatusCode, String) {
+    let db = db.get().db().clone();
+    if !db.cf_names().contains(&collection.as_str()) {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("No collection {collection}\n"),
+        );
+    }
+    info!(%collection, "Compacting collection");
+    let result = {
+        let collection = collection.clone();
+        spawn_blocking(move || db.compact(&collection)).await
+    };
+    match result {
+        Ok(Ok(())) => {
+            info!(%collection, "Collection compacted");
+            (StatusCode::OK, format!("Compacted {collection}\n"))
+        }
+        Ok(Err(err)) => {
+            warn!(%collection, %err, "Compaction failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{err}\n"))
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{err}\n")),
+    }
+}
+
+async fn backup_handler(
+    Path(name): Path<String>,
+    Extension(db): Extension<SwappableDatabase>,
+    Extension(backups): Extension<Option<BackupOptions>>,
+) -> (StatusCode, String) {
+    let backups = match backups {
+        Some(backups) => backups,
+        None => return (StatusCode::NOT_FOUND, "Backups are disabled\n".to_string()),
+    };
+    if !backup::is_valid_name(&name) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid backup name {name:?}\n"),
+        );
+    }
+    let db = db.get().db().clone();
+    info!(%name, "Creating backup");
+    let result = {
+        let name = name.clone();
+        spawn_blocking(move || backup::create(&db, &backups, &name)).await
+    };
+    match result {
+        Ok(Ok(path)) => (StatusCode::OK, format!("Backed up to {}\n", path.display())),
+        Ok(Err(err)) => {
+            warn!(%name, %err, "Backup failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}\n"))
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{err}\n")),
+    }
+}
+
+/// Serves maintenance requests on localhost, e.g. `POST /compact/users` once a rebuild is
+/// swapped in, or `POST /backup/before-upgrade` to back up to `--backups` on demand. Such
+/// backups are only pruned if named `after-*` like those taken after each archive.
+pub async fn serve(
+    db: &SwappableDatabase,
+    port: u16,
+    backups: Option<BackupOptions>,
+) -> Result<()> {
+    let app = Router::new()
+        .route("/compact/:collection", post(compact_handler))
+        .route("/backup/:name", post(backup_handler))
+        .layer(Extension(db.clone()))
+        .layer(Extension(backups));
+    axum::Server::bind(&([127, 0, 0, 1], port).into())
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use rkyvdb::{CheckpointDir, Database};
+use tracing::info;
+
+use super::rebuild::{new_generation, point_to_generation};
+
+/// Where to keep backups taken after each processed archive.
+#[derive(Clone)]
+pub struct BackupOptions {
+    pub dir: PathBuf,
+    /// Number of most recent backups kept when pruning.
+    pub keep: usize,
+}
+
+const ARCHIVE_BACKUP_PREFIX: &str = "after-";
+
+/// Backs up the state after the archive of the given month and prunes old backups of that
+/// kind. Backups created with `create` are left alone.
+pub fn create_after_archive(db: &Database, options: &BackupOptions, month: &str) -> Result<()> {
+    create(db, options, &format!("{ARCHIVE_BACKUP_PREFIX}{month}"))?;
+    for name in CheckpointDir::new(&options.dir).prune(ARCHIVE_BACKUP_PREFIX, options.keep)? {
+        info!(%name, "Backup pruned");
+    }
+    Ok(())
+}
+
+/// Whether `name` can be used as a backup name, i.e. names a directory inside the backups one.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// Backs up the current state as `name`, replacing an existing backup with that name.
+pub fn create(db: &Database, options: &BackupOptions, name: &str) -> Result<PathBuf> {
+    if !is_valid_name(name) {
+        bail!("Invalid backup name {name:?}");
+    }
+    let path = CheckpointDir::new(&options.dir).create(db, name)?;
+    info!(path = %path.display(), "Backup created");
+    Ok(path)
+}
+
+pub fn list(dir: &Path) -> Result<()> {
+    let names = CheckpointDir::new(dir).list()?;
+    if names.is_empty() {
+        println!("No backups in {}", dir.display());
+    }
+    for name in names {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+/// Copies backup `name` into a new generation next to `db_path` and points `db_path` at it.
+/// The current database is left for `remove_stale_generations`.
+pub fn restore(dir: &Path, name: &str, db_path: &Path) -> Result<()> {
+    let generation = new_generation(db_path);
+    CheckpointDir::new(dir)
+        .restore(name, &generation)
+        .with_context(|| format!("Failed to restore backup {name}"))?;
+    point_to_generation(db_path, &generation)?;
+    info!(%name, path = %generation.display(), "Backup restored");
+    Ok(())
+}
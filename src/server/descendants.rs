@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use rkyvdb::{CaseInsensitiveString, Collection, Database, Store};
+use tracing::info;
+
+use crate::{
+    data::{Child, ChildInfo, DescendantsInfo, SubtreeSize, User},
+    util::{Root, ROOTS},
+};
+
+/// Records written per commit while building `Child` and `SubtreeSize`.
+const BUILD_BATCH_SIZE: usize = 10_000;
+
+fn child_key(root: &Root, parent: &str, child: &str) -> <Child as Collection>::KeyType {
+    (root.name.to_string(), parent.into(), child.into())
+}
+
+fn subtree_size_key(root: &Root, id: &str) -> <SubtreeSize as Collection>::KeyType {
+    (root.name.to_string(), id.into())
+}
+
+fn subtree_size(root: &Root, id: &str, db: &impl Store) -> Result<u64> {
+    Ok(SubtreeSize::get(subtree_size_key(root, id), db)?.map_or(0, |size| size.0))
+}
+
+/// Ids of `id` and everyone above them in the root's tree, following latest links.
+fn ancestors(root: &Root, id: &str, db: &impl Store) -> Result<Vec<String>> {
+    let mut ancestors = vec![id.to_string()];
+    loop {
+        let parent = User::get(ancestors.last().unwrap().as_str(), db)?
+            .context("Ancestor should be in DB")?
+            .erdos_links
+            .remove(root.name)
+            .and_then(|mut erdos_links| erdos_links.pop())
+            .map(|erdos_link| erdos_link.loser_id);
+        match parent {
+            Some(parent) => ancestors.push(parent),
+            None => return Ok(ancestors),
+        }
+    }
+}
+
+fn add_to_subtree(root: &Root, id: &str, delta: i64, db: &impl Store) -> Result<()> {
+    for ancestor in ancestors(root, id, db)? {
+        SubtreeSize::modify(subtree_size_key(root, &ancestor), db, |size| {
+            let size = size.map_or(0, |size| size.0);
+            Some(SubtreeSize(size.saturating_add_signed(delta)))
+        })?;
+    }
+    Ok(())
+}
+
+/// Moves `child` with their whole subtree from `old_parent` to `new_parent`. Must be called
+/// after `child`'s new latest link to `new_parent` is stored.
+pub fn reparent(
+    root: &Root,
+    child: &str,
+    old_parent: Option<&str>,
+    new_parent: &str,
+    db: &impl Store,
+) -> Result<()> {
+    let moved = subtree_size(root, child, db)? + 1;
+    if let Some(old_parent) = old_parent {
+        Child::delete(child_key(root, old_parent, child), db)?;
+        add_to_subtree(root, old_parent, -(moved as i64), db)?;
+    }
+    Child::put(
+        child_key(root, new_parent, child),
+        &Child {
+            id: child.to_string(),
+        },
+        db,
+    )?;
+    add_to_subtree(root, new_parent, moved as i64, db)
+}
+
+pub fn descendants_info(root: &Root, id: &str, db: &Database) -> Result<DescendantsInfo> {
+    let prefix = (root.name.to_string(), CaseInsensitiveString::from(id));
+    let children = Child::prefix_iter_by(&prefix, db)?
+        .map(|entry| {
+            let (_, child) = entry?;
+            Ok(ChildInfo {
+                subtree_size: subtree_size(root, &child.id, db)?,
+                id: child.id,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(DescendantsInfo {
+        id: id.to_string(),
+        subtree_size: subtree_size(root, id, db)?,
+        children,
+    })
+}
+
+/// Replaces `Child` and `SubtreeSize` with the trees of latest links stored in `User`. Needed
+/// once for databases whose users were ingested before `reparent` kept them up to date.
+pub fn build(db: &Database) -> Result<()> {
+    info!("Building descendants from users");
+    // Parent of every user with a link, per root, keyed by lowercase id like the collections.
+    let mut parents: Vec<HashMap<String, String>> = vec![HashMap::new(); ROOTS.len()];
+    let mut ids = HashMap::new();
+    for entry in User::iter(db)? {
+        let (_, user) = entry?;
+        for (root_parents, root) in parents.iter_mut().zip(ROOTS) {
+            let parent = user
+                .erdos_links
+                .get(root.name)
+                .and_then(|erdos_links| erdos_links.last());
+            if let Some(parent) = parent {
+                root_parents.insert(user.id.to_lowercase(), parent.loser_id.to_lowercase());
+                ids.insert(user.id.to_lowercase(), user.id.clone());
+            }
+        }
+    }
+
+    let mut batch = db.batch();
+    for entry in Child::iter(db)? {
+        let (key, _) = entry?;
+        Child::delete(key, &batch)?;
+        if batch.len() >= BUILD_BATCH_SIZE {
+            batch.commit()?;
+        }
+    }
+    for entry in SubtreeSize::iter(db)? {
+        let (key, _) = entry?;
+        SubtreeSize::delete(key, &batch)?;
+        if batch.len() >= BUILD_BATCH_SIZE {
+            batch.commit()?;
+        }
+    }
+    batch.commit()?;
+    let mut users = 0;
+    for (root_parents, root) in parents.iter().zip(ROOTS) {
+        // Every user counts once towards each of their ancestors.
+        let mut subtree_sizes: HashMap<&str, u64> = HashMap::new();
+        for (child, parent) in root_parents {
+            let id = &ids[child];
+            Child::put(
+                child_key(root, parent, id),
+                &Child { id: id.clone() },
+                &batch,
+            )?;
+            if batch.len() >= BUILD_BATCH_SIZE {
+                batch.commit()?;
+            }
+            let mut ancestor = Some(parent);
+            while let Some(id) = ancestor {
+                *subtree_sizes.entry(id.as_str()).or_default() += 1;
+                ancestor = root_parents.get(id);
+            }
+        }
+        users += subtree_sizes.len();
+        for (id, size) in subtree_sizes {
+            SubtreeSize::put(subtree_size_key(root, id), &SubtreeSize(size), &batch)?;
+            if batch.len() >= BUILD_BATCH_SIZE {
+                batch.commit()?;
+            }
+        }
+    }
+    batch.commit()?;
+    info!(users, "Descendants built");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::Utc;
+    use rkyvdb::{Collection, Database};
+
+    use super::{descendants_info, reparent};
+    use crate::{
+        data::{ErdosLink, PlayerInfo, Termination, TimeControl, TimeControlType, User},
+        util::DEFAULT_ROOT,
+    };
+
+    fn player_info() -> PlayerInfo {
+        PlayerInfo {
+            title: "".to_string(),
+            rating: 2000,
+            rating_change: 0,
+        }
+    }
+
+    /// Stores a new latest link of `child` to `parent` and reparents `child`, like ingestion.
+    fn link(db: &Database, child: &str, parent: &str) {
+        let mut user = User::get(child, db).unwrap().unwrap_or(User {
+            id: child.to_string(),
+            erdos_links: BTreeMap::new(),
+        });
+        let erdos_links = user
+            .erdos_links
+            .entry(DEFAULT_ROOT.name.to_string())
+            .or_default();
+        let old_parent = erdos_links.last().map(|link| link.loser_id.clone());
+        erdos_links.push(ErdosLink {
+            erdos_number: 0,
+            loser_id: parent.to_string(),
+            time: Utc::now(),
+            winner_info: player_info(),
+            loser_info: player_info(),
+            game_id: "".to_string(),
+            move_count: 40,
+            time_control: TimeControl {
+                game_type: TimeControlType::Blitz,
+                main: 180,
+                increment: 0,
+            },
+            winner_is_white: true,
+            termination: Termination::Resign,
+        });
+        User::put(child, &user, db).unwrap();
+        reparent(DEFAULT_ROOT, child, old_parent.as_deref(), parent, db).unwrap();
+    }
+
+    fn subtree_size(db: &Database, id: &str) -> u64 {
+        descendants_info(DEFAULT_ROOT, id, db).unwrap().subtree_size
+    }
+
+    #[test]
+    fn reparent_moves_subtree() {
+        let db = crate::server::database_builder().open_in_memory().unwrap();
+        let root = DEFAULT_ROOT.id;
+        User::put(
+            root,
+            &User {
+                id: root.to_string(),
+                erdos_links: BTreeMap::new(),
+            },
+            &db,
+        )
+        .unwrap();
+        link(&db, "alice", root);
+        link(&db, "dave", root);
+        link(&db, "bob", "alice");
+        link(&db, "carol", "bob");
+        assert_eq!(subtree_size(&db, root), 4);
+        assert_eq!(subtree_size(&db, "alice"), 2);
+        assert_eq!(subtree_size(&db, "dave"), 0);
+
+        link(&db, "bob", "dave");
+        assert_eq!(subtree_size(&db, root), 4);
+        assert_eq!(subtree_size(&db, "alice"), 0);
+        assert_eq!(subtree_size(&db, "dave"), 2);
+        assert_eq!(subtree_size(&db, "bob"), 1);
+        let children = |id: &str| -> Vec<String> {
+            descendants_info(DEFAULT_ROOT, id, &db)
+                .unwrap()
+                .children
+                .into_iter()
+                .map(|child| child.id)
+                .collect()
+        };
+        assert!(children("alice").is_empty());
+        assert_eq!(children("dave"), ["bob"]);
+
+        // Building from users gives the same trees.
+        super::build(&db).unwrap();
+        assert_eq!(subtree_size(&db, root), 4);
+        assert_eq!(subtree_size(&db, "alice"), 0);
+        assert_eq!(subtree_size(&db, "dave"), 2);
+        assert!(children("alice").is_empty());
+        assert_eq!(children("dave"), ["bob"]);
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use rkyvdb::DumpFormat;
+
+use super::database_builder;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// Newline-delimited JSON.
+    Json,
+    MessagePack,
+}
+
+impl From<Format> for DumpFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Json => DumpFormat::Json,
+            Format::MessagePack => DumpFormat::MessagePack,
+        }
+    }
+}
+
+fn print_counts(verb: &str, counts: &[(&str, u64)]) {
+    for (collection, records) in counts {
+        println!("{collection}: {verb} {records} records");
+    }
+}
+
+/// Writes every collection of the database at `db_path` to `dump_path`. The database is
+/// opened read-only, so this can run next to a server ingesting into it.
+pub fn export(db_path: &Path, dump_path: &Path, format: Format) -> Result<()> {
+    let db = database_builder().open_read_only(db_path.to_str().context("Non UTF-8 DB path")?)?;
+    let out = File::create(dump_path)
+        .with_context(|| format!("Failed to create {}", dump_path.display()))?;
+    let counts = db.export(BufWriter::new(out), format.into())?;
+    print_counts("exported", &counts);
+    Ok(())
+}
+
+/// Creates a database at `db_path` from the dump at `dump_path`.
+pub fn import(db_path: &Path, dump_path: &Path, format: Format) -> Result<()> {
+    if db_path.exists() {
+        bail!(
+            "{} already exists, import needs a fresh database",
+            db_path.display()
+        );
+    }
+    let input =
+        File::open(dump_path).with_context(|| format!("Failed to open {}", dump_path.display()))?;
+    let db = database_builder().open(db_path.to_str().context("Non UTF-8 DB path")?)?;
+    let counts = db.import(BufReader::new(input), format.into())?;
+    print_counts("imported", &counts);
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::data::{Termination, TimeControlType};
+
+/// Which rated games may improve a player's number. Loaded from a TOML file, any field left
+/// out keeps its default value:
+///
+/// ```toml
+/// time_controls = ["Rapid", "Classical"]
+/// min_plies = 40
+/// min_loser_rating = 1500
+/// max_rating_gap = 400
+/// terminations = ["Checkmate", "Resign"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EligibilityRules {
+    pub time_controls: Vec<TimeControlType>,
+    pub min_plies: u32,
+    pub min_loser_rating: Option<u32>,
+    pub max_rating_gap: Option<u32>,
+    pub terminations: Vec<Termination>,
+}
+
+impl Default for EligibilityRules {
+    fn default() -> Self {
+        EligibilityRules {
+            time_controls: vec![
+                TimeControlType::Blitz,
+                TimeControlType::Rapid,
+                TimeControlType::Classical,
+            ],
+            min_plies: 20,
+            min_loser_rating: None,
+            max_rating_gap: None,
+            terminations: vec![
+                Termination::Checkmate,
+                Termination::Resign,
+                Termination::Time,
+            ],
+        }
+    }
+}
+
+impl EligibilityRules {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&config).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Checks a game's ratings, returning the skip reason if they're out of bounds.
+    pub fn check_ratings(&self, winner_rating: u32, loser_rating: u32) -> Result<(), &'static str> {
+        if self
+            .min_loser_rating
+            .map_or(false, |min_rating| loser_rating < min_rating)
+        {
+            return Err("rating: loser too low");
+        }
+        if self.max_rating_gap.map_or(false, |max_gap| {
+            winner_rating.abs_diff(loser_rating) > max_gap
+        }) {
+            return Err("rating: gap too big");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::EligibilityRules;
+    use crate::data::{Termination, TimeControlType};
+
+    fn load(config: &str) -> anyhow::Result<EligibilityRules> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules.toml");
+        fs::write(&path, config).unwrap();
+        EligibilityRules::load(&path)
+    }
+
+    #[test]
+    fn missing_fields_keep_defaults() {
+        let rules = load("min_plies = 40\nterminations = [\"Resign\"]").unwrap();
+        assert_eq!(rules.min_plies, 40);
+        assert_eq!(rules.terminations, [Termination::Resign]);
+        assert_eq!(
+            rules.time_controls,
+            EligibilityRules::default().time_controls
+        );
+        assert_eq!(rules.min_loser_rating, None);
+
+        let rules = load("").unwrap();
+        assert_eq!(rules.min_plies, 20);
+        assert_eq!(
+            rules.time_controls,
+            [
+                TimeControlType::Blitz,
+                TimeControlType::Rapid,
+                TimeControlType::Classical
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        let err = load("min_plies = ").unwrap_err();
+        assert!(format!("{err:#}").contains("Failed to parse"), "{err:#}");
+        let err = load("min_moves = 40").unwrap_err();
+        assert!(format!("{err:#}").contains("min_moves"), "{err:#}");
+        let err = load("terminations = [\"Stalemate\"]").unwrap_err();
+        assert!(format!("{err:#}").contains("Stalemate"), "{err:#}");
+        let dir = tempfile::tempdir().unwrap();
+        let err = EligibilityRules::load(&dir.path().join("missing.toml")).unwrap_err();
+        assert!(format!("{err:#}").contains("Failed to read"), "{err:#}");
+    }
+
+    #[test]
+    fn checks_ratings() {
+        let default = EligibilityRules::default();
+        assert_eq!(default.check_ratings(3000, 600), Ok(()));
+
+        let rules = EligibilityRules {
+            min_loser_rating: Some(1500),
+            max_rating_gap: Some(400),
+            ..EligibilityRules::default()
+        };
+        assert_eq!(rules.check_ratings(1600, 1500), Ok(()));
+        assert_eq!(
+            rules.check_ratings(1600, 1499),
+            Err("rating: loser too low")
+        );
+        assert_eq!(rules.check_ratings(1900, 1500), Ok(()));
+        assert_eq!(rules.check_ratings(1901, 1500), Err("rating: gap too big"));
+        // The gap counts both ways.
+        assert_eq!(rules.check_ratings(1500, 1901), Err("rating: gap too big"));
+    }
+}
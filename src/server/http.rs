@@ -1,13 +1,23 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use axum::{extract::Path, http::StatusCode, routing::get, Extension, Router};
 use headers::{CacheControl, ContentType, HeaderMap, HeaderMapExt};
 use include_dir::{include_dir, Dir};
-use rkyvdb::{Collection, Database};
+use rkyvdb::{AsyncDatabase, Collection, Database};
 use tracing::Level;
 
-use crate::data::{ErdosChains, ErdosLink, ServerMetadata, User};
+use super::{
+    descendants::descendants_info, process_archive::archive_month, rebuild::SwappableDatabase,
+    winning_path::find_winning_path,
+};
+use crate::{
+    data::{ErdosChains, ErdosLink, ServerMetadata, Statistics, User, WinningPath},
+    util::{Root, DEFAULT_ROOT},
+};
 
 static DIST: Dir = include_dir!("$CARGO_MANIFEST_DIR/generated/dist");
 
@@ -25,53 +35,200 @@ async fn static_handler(Path(path): Path<String>) -> (StatusCode, HeaderMap, &'s
     }
 }
 
-#[tracing::instrument(skip_all, fields(erdos_number = %erdos_link.erdos_number))]
-fn expand_erdos_chain(erdos_link: ErdosLink, db: &Database) -> Result<Vec<ErdosLink>> {
-    let mut erdos_links = vec![erdos_link];
-    for erdos_number in (1..erdos_links[0].erdos_number).rev() {
-        let next_user = User::get(erdos_links.last().unwrap().loser_id.as_str(), db)?
-            .context("Broken chain in DB")?;
-        let next_erdos_link = next_user
-            .erdos_links
-            .into_iter()
-            .find(|erdos_link| erdos_link.erdos_number == erdos_number)
-            .context("Broken chain in DB")?;
-        erdos_links.push(next_erdos_link);
+/// Follows each of the user's links to the root down to erdos number 1. All chains are
+/// extended a hop at a time, reading the losers of a hop with one multi-get, and users shared
+/// by several chains are only read once.
+#[tracing::instrument(skip_all, fields(root = root.name, user = %user.id))]
+fn build_erdos_chains(root: &Root, mut user: User, db: &Database) -> Result<ErdosChains> {
+    let mut erdos_chains: Vec<Vec<ErdosLink>> = user
+        .erdos_links
+        .remove(root.name)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|erdos_link| vec![erdos_link])
+        .collect();
+    // Links to the root of users read so far, by lowercase id.
+    let mut links_by_user: HashMap<String, Vec<ErdosLink>> = HashMap::new();
+    loop {
+        let unfinished = || {
+            erdos_chains
+                .iter()
+                .map(|erdos_chain| erdos_chain.last().unwrap())
+                .filter(|erdos_link| erdos_link.erdos_number > 1)
+        };
+        if unfinished().next().is_none() {
+            break;
+        }
+        let to_read: BTreeSet<String> = unfinished()
+            .map(|erdos_link| erdos_link.loser_id.to_lowercase())
+            .filter(|id| !links_by_user.contains_key(id))
+            .collect();
+        let users = User::multi_get(to_read.iter(), db)?;
+        for (id, loser) in to_read.into_iter().zip(users) {
+            let mut loser = loser.context("Broken chain in DB")?;
+            let erdos_links = loser.erdos_links.remove(root.name).unwrap_or_default();
+            links_by_user.insert(id, erdos_links);
+        }
+        for erdos_chain in &mut erdos_chains {
+            let last = erdos_chain.last().unwrap();
+            if last.erdos_number <= 1 {
+                continue;
+            }
+            let next_erdos_link = links_by_user[&last.loser_id.to_lowercase()]
+                .iter()
+                .find(|erdos_link| erdos_link.erdos_number == last.erdos_number - 1)
+                .context("Broken chain in DB")?
+                .clone();
+            erdos_chain.push(next_erdos_link);
+        }
     }
-    Ok(erdos_links)
-}
-
-#[tracing::instrument(skip_all, fields(user = %user.id))]
-fn build_erdos_chains(user: User, db: &Database) -> Result<ErdosChains> {
+    erdos_chains.reverse();
     Ok(ErdosChains {
         id: user.id.to_string(),
-        erdos_chains: user
-            .erdos_links
-            .into_iter()
-            .map(|x| expand_erdos_chain(x, db))
-            .rev()
-            .collect::<Result<Vec<_>>>()?,
+        erdos_chains,
     })
 }
 
+async fn erdos_chains_response(
+    root: &'static Root,
+    id: String,
+    db: AsyncDatabase,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(ContentType::octet_stream());
+    headers.typed_insert(CacheControl::new().with_max_age(Duration::from_secs(60 * 60)));
+    let erdos_chains = db
+        .run(move |db| -> Result<_> {
+            User::get(id.as_str(), db)?
+                .map(|user| build_erdos_chains(root, user, db))
+                .transpose()
+        })
+        .await
+        .unwrap();
+    if let Some(erdos_chains) = erdos_chains {
+        (
+            StatusCode::OK,
+            headers,
+            rmp_serde::encode::to_vec(&erdos_chains).unwrap(),
+        )
+    } else {
+        (StatusCode::NOT_FOUND, headers, vec![])
+    }
+}
+
 async fn erdos_chains_handler(
+    Path((root, id)): Path<(String, String)>,
+    Extension(db): Extension<SwappableDatabase>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    if let Some(root) = Root::find(&root) {
+        erdos_chains_response(root, id, db.get()).await
+    } else {
+        (StatusCode::NOT_FOUND, HeaderMap::new(), vec![])
+    }
+}
+
+async fn default_erdos_chains_handler(
     Path(id): Path<String>,
-    Extension(db): Extension<Database>,
+    Extension(db): Extension<SwappableDatabase>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    erdos_chains_response(DEFAULT_ROOT, id, db.get()).await
+}
+
+async fn descendants_response(
+    root: &'static Root,
+    id: String,
+    db: AsyncDatabase,
 ) -> (StatusCode, HeaderMap, Vec<u8>) {
     let mut headers = HeaderMap::new();
     headers.typed_insert(ContentType::octet_stream());
     headers.typed_insert(CacheControl::new().with_max_age(Duration::from_secs(60 * 60)));
-    if let Some(user) = User::get(&id, &db).unwrap() {
+    let descendants = db
+        .run(move |db| -> Result<_> {
+            if User::get(id.as_str(), db)?.is_none() {
+                return Ok(None);
+            }
+            descendants_info(root, &id, db).map(Some)
+        })
+        .await
+        .unwrap();
+    if let Some(descendants) = descendants {
         (
             StatusCode::OK,
             headers,
-            rmp_serde::encode::to_vec(&build_erdos_chains(user, &db).unwrap()).unwrap(),
+            rmp_serde::encode::to_vec(&descendants).unwrap(),
         )
     } else {
         (StatusCode::NOT_FOUND, headers, vec![])
     }
 }
 
+async fn descendants_handler(
+    Path((root, id)): Path<(String, String)>,
+    Extension(db): Extension<SwappableDatabase>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    if let Some(root) = Root::find(&root) {
+        descendants_response(root, id, db.get()).await
+    } else {
+        (StatusCode::NOT_FOUND, HeaderMap::new(), vec![])
+    }
+}
+
+async fn default_descendants_handler(
+    Path(id): Path<String>,
+    Extension(db): Extension<SwappableDatabase>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    descendants_response(DEFAULT_ROOT, id, db.get()).await
+}
+
+/// Not found unless all archives were processed with `--record-wins`, as paths would be
+/// missing otherwise.
+async fn winning_path_handler(
+    Path((from, to)): Path<(String, String)>,
+    Extension(db): Extension<SwappableDatabase>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let db = db.get();
+    let wins_recorded = db
+        .get::<ServerMetadata>(())
+        .await
+        .unwrap()
+        .map_or(false, |metadata| metadata.wins_recorded);
+    if !wins_recorded {
+        return (StatusCode::NOT_FOUND, HeaderMap::new(), vec![]);
+    }
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(ContentType::octet_stream());
+    headers.typed_insert(CacheControl::new().with_max_age(Duration::from_secs(60 * 60)));
+    let path = {
+        let (from, to) = (from.clone(), to.clone());
+        db.run(move |db| find_winning_path(&from, &to, db))
+            .await
+            .unwrap()
+    };
+    let winning_path = WinningPath {
+        from,
+        to,
+        path: path.unwrap_or_default(),
+    };
+    (
+        StatusCode::OK,
+        headers,
+        rmp_serde::encode::to_vec(&winning_path).unwrap(),
+    )
+}
+
+async fn stats_handler(Extension(db): Extension<SwappableDatabase>) -> (HeaderMap, Vec<u8>) {
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(ContentType::octet_stream());
+    headers.typed_insert(CacheControl::new().with_max_age(Duration::from_secs(60 * 60)));
+    let statistics = db
+        .get()
+        .get::<Statistics>(())
+        .await
+        .unwrap()
+        .unwrap_or_default();
+    (headers, rmp_serde::encode::to_vec(&statistics).unwrap())
+}
+
 async fn index_handler() -> (HeaderMap, &'static [u8]) {
     let mut header_map = HeaderMap::new();
     header_map.typed_insert(CacheControl::new().with_max_age(Duration::from_secs(60)));
@@ -79,22 +236,32 @@ async fn index_handler() -> (HeaderMap, &'static [u8]) {
     (header_map, DIST.get_file("index.html").unwrap().contents())
 }
 
-async fn last_processed_handler(Extension(db): Extension<Database>) -> (HeaderMap, String) {
+async fn last_processed_handler(
+    Extension(db): Extension<SwappableDatabase>,
+) -> (HeaderMap, String) {
     let mut header_map = HeaderMap::new();
     header_map.typed_insert(CacheControl::new().with_max_age(Duration::from_secs(60)));
     header_map.typed_insert(ContentType::text());
-    let last_archive = ServerMetadata::get((), &db)
+    let last_archive = db
+        .get()
+        .get::<ServerMetadata>(())
+        .await
         .unwrap()
         .map(|x| x.last_processed_archive)
         .unwrap_or_default();
-    let last_time = last_archive[(last_archive.len() - 15)..(last_archive.len() - 8)].to_string();
+    let last_time = archive_month(&last_archive).unwrap_or_default().to_string();
     (header_map, last_time)
 }
 
-pub async fn serve(db: &Database) -> Result<()> {
+pub async fn serve(db: &SwappableDatabase, port: u16) -> Result<()> {
     let app = Router::new()
-        .route("/api/erdos_chains/:id", get(erdos_chains_handler))
+        .route("/api/erdos_chains/:id", get(default_erdos_chains_handler))
+        .route("/api/:root/erdos_chains/:id", get(erdos_chains_handler))
+        .route("/api/descendants/:id", get(default_descendants_handler))
+        .route("/api/:root/descendants/:id", get(descendants_handler))
         .route("/api/last_processed", get(last_processed_handler))
+        .route("/api/winning_path/:from/:to", get(winning_path_handler))
+        .route("/api/stats", get(stats_handler))
         .route("/assets/*path", get(static_handler))
         .fallback(index_handler)
         .layer(Extension(db.clone()))
@@ -104,8 +271,187 @@ pub async fn serve(db: &Database) -> Result<()> {
                 .on_request(tower_http::trace::DefaultOnRequest::new().level(Level::INFO))
                 .on_response(tower_http::trace::DefaultOnResponse::new().level(Level::INFO)),
         );
-    axum::Server::bind(&([127, 0, 0, 1], 3001).into())
+    axum::Server::bind(&([127, 0, 0, 1], port).into())
         .serve(app.into_make_service())
         .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use axum::{extract::Path, http::StatusCode, Extension};
+    use chrono::{TimeZone, Utc};
+    use rkyvdb::{Collection, Database};
+
+    use super::{
+        build_erdos_chains, descendants_handler, erdos_chains_handler, last_processed_handler,
+        winning_path_handler,
+    };
+    use crate::{
+        data::{
+            DescendantsInfo, ErdosChains, ErdosLink, PlayerInfo, ServerMetadata, Termination,
+            TimeControl, TimeControlType, User,
+        },
+        server::{descendants, rebuild::SwappableDatabase},
+        util::DEFAULT_ROOT,
+    };
+
+    fn erdos_link(erdos_number: u32, loser_id: &str, hour: u32) -> ErdosLink {
+        let player_info = PlayerInfo {
+            title: "".to_string(),
+            rating: 2000,
+            rating_change: 0,
+        };
+        ErdosLink {
+            erdos_number,
+            loser_id: loser_id.to_string(),
+            time: Utc.with_ymd_and_hms(2014, 1, 1, hour, 0, 0).unwrap(),
+            winner_info: player_info.clone(),
+            loser_info: player_info,
+            game_id: format!("game{hour}"),
+            move_count: 40,
+            time_control: TimeControl {
+                game_type: TimeControlType::Blitz,
+                main: 180,
+                increment: 0,
+            },
+            winner_is_white: true,
+            termination: Termination::Resign,
+        }
+    }
+
+    fn put_user(db: &Database, id: &str, erdos_links: Vec<ErdosLink>) {
+        let user = User {
+            id: id.to_string(),
+            erdos_links: BTreeMap::from([(DEFAULT_ROOT.name.to_string(), erdos_links)]),
+        };
+        User::put(id, &user, db).unwrap();
+    }
+
+    /// alice beat the root, bob beat alice, carol beat bob and later alice. eve beat someone
+    /// missing from the DB.
+    fn fixture() -> Database {
+        let db = crate::server::database_builder().open_in_memory().unwrap();
+        put_user(&db, "alice", vec![erdos_link(1, DEFAULT_ROOT.id, 1)]);
+        put_user(&db, "bob", vec![erdos_link(2, "Alice", 2)]);
+        put_user(
+            &db,
+            "carol",
+            vec![erdos_link(3, "bob", 3), erdos_link(2, "alice", 4)],
+        );
+        put_user(&db, "eve", vec![erdos_link(3, "ghost", 5)]);
+        db
+    }
+
+    /// Erdos number and game of each link of each chain of `id`.
+    fn chains(db: &Database, id: &str) -> anyhow::Result<Vec<Vec<(u32, String)>>> {
+        let user = User::get(id, db).unwrap().unwrap();
+        let erdos_chains = build_erdos_chains(DEFAULT_ROOT, user, db)?;
+        Ok(erdos_chains
+            .erdos_chains
+            .into_iter()
+            .map(|chain| {
+                chain
+                    .into_iter()
+                    .map(|link| (link.erdos_number, link.game_id))
+                    .collect()
+            })
+            .collect())
+    }
+
+    #[test]
+    fn follows_links_down_to_the_root() {
+        let db = fixture();
+        let hop = |number: u32, hour: u32| (number, format!("game{hour}"));
+        assert_eq!(chains(&db, "alice").unwrap(), [vec![hop(1, 1)]]);
+        assert_eq!(chains(&db, "bob").unwrap(), [vec![hop(2, 2), hop(1, 1)]]);
+        // Latest link first, both reusing alice's link.
+        assert_eq!(
+            chains(&db, "carol").unwrap(),
+            [
+                vec![hop(2, 4), hop(1, 1)],
+                vec![hop(3, 3), hop(2, 2), hop(1, 1)]
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_user_breaks_chain() {
+        let db = fixture();
+        let err = chains(&db, "eve").unwrap_err();
+        assert!(err.to_string().contains("Broken chain"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn erdos_chains_handler_finds_users_case_insensitively() {
+        let db = SwappableDatabase::new(fixture());
+        let get = |root: &str, id: &str| {
+            erdos_chains_handler(
+                Path((root.to_string(), id.to_string())),
+                Extension(db.clone()),
+            )
+        };
+        let (status, _, body) = get("magnus", "CAROL").await;
+        assert_eq!(status, StatusCode::OK);
+        let erdos_chains: ErdosChains = rmp_serde::decode::from_slice(&body).unwrap();
+        assert_eq!(erdos_chains.id, "carol");
+        assert_eq!(erdos_chains.erdos_chains.len(), 2);
+        assert_eq!(get("magnus", "nobody").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(get("nobody", "carol").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn descendants_handler_lists_children() {
+        let db = fixture();
+        descendants::build(&db).unwrap();
+        let db = SwappableDatabase::new(db);
+        let (status, _, body) = descendants_handler(
+            Path(("magnus".to_string(), "alice".to_string())),
+            Extension(db.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let descendants: DescendantsInfo = rmp_serde::decode::from_slice(&body).unwrap();
+        assert_eq!(descendants.subtree_size, 2);
+        let children: Vec<_> = descendants
+            .children
+            .iter()
+            .map(|child| (child.id.as_str(), child.subtree_size))
+            .collect();
+        assert_eq!(children, [("bob", 0), ("carol", 0)]);
+    }
+
+    #[tokio::test]
+    async fn winning_paths_need_recorded_wins() {
+        let db = fixture();
+        let get = |db: Database| {
+            winning_path_handler(
+                Path(("carol".to_string(), DEFAULT_ROOT.id.to_string())),
+                Extension(SwappableDatabase::new(db)),
+            )
+        };
+        assert_eq!(get(db.clone()).await.0, StatusCode::NOT_FOUND);
+        let metadata = ServerMetadata {
+            wins_recorded: true,
+            ..ServerMetadata::default()
+        };
+        ServerMetadata::put((), &metadata, &db).unwrap();
+        assert_eq!(get(db).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn last_processed_is_the_archive_month() {
+        let db = fixture();
+        let get =
+            |db: &Database| last_processed_handler(Extension(SwappableDatabase::new(db.clone())));
+        assert_eq!(get(&db).await.1, "");
+        let metadata = ServerMetadata {
+            last_processed_archive: "lichess_db_standard_rated_2014-01.pgn.zst".to_string(),
+            ..ServerMetadata::default()
+        };
+        ServerMetadata::put((), &metadata, &db).unwrap();
+        assert_eq!(get(&db).await.1, "2014-01");
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    io::{self, Read},
+    thread::sleep,
+    time::Duration,
+};
+
+use anyhow::{ensure, Context, Result};
+use reqwest::{
+    blocking::{Client, Response},
+    header::RANGE,
+    StatusCode,
+};
+use tracing::warn;
+
+const MAX_RETRIES: u32 = 10;
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest wait for a single read, after which the connection is considered stalled.
+const READ_TIMEOUT: Duration = Duration::from_secs(120);
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// Blocking reader over an HTTP body which reconnects with a `Range` request when the
+/// connection breaks mid-transfer, so the consumer sees one uninterrupted stream.
+pub struct HttpReader {
+    client: Client,
+    url: String,
+    response: Response,
+    offset: u64,
+    /// Reconnects since the last successful read.
+    reconnects: u32,
+}
+
+fn connect(client: &Client, url: &str, offset: u64) -> Result<Response> {
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={offset}-"));
+    }
+    let response = request
+        .send()
+        .with_context(|| format!("Failed to request {url}"))?;
+    let status = response.status();
+    ensure!(status.is_success(), "Unexpected status {status} for {url}");
+    ensure!(
+        offset == 0 || status == StatusCode::PARTIAL_CONTENT,
+        "Server ignored range request for {url}"
+    );
+    Ok(response)
+}
+
+fn connect_with_retries(client: &Client, url: &str, offset: u64) -> Result<Response> {
+    let mut attempt = 0;
+    loop {
+        match connect(client, url, offset) {
+            Ok(response) => return Ok(response),
+            Err(err) if attempt < MAX_RETRIES => {
+                attempt += 1;
+                warn!(%url, offset, attempt, "Connection failed, retrying: {err:#}");
+                sleep(RETRY_DELAY);
+            }
+            Err(err) => return Err(err.context(format!("Giving up after {attempt} retries"))),
+        }
+    }
+}
+
+impl HttpReader {
+    pub fn open(url: &str) -> Result<Self> {
+        // The blocking client applies `timeout` to each read of the body rather than to the whole
+        // download, which can take hours.
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(READ_TIMEOUT)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .build()
+            .context("Failed to build HTTP client")?;
+        let response = connect_with_retries(&client, url, 0)?;
+        Ok(HttpReader {
+            client,
+            url: url.to_string(),
+            response,
+            offset: 0,
+            reconnects: 0,
+        })
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.response.read(buf) {
+                Ok(read) => {
+                    self.offset += read as u64;
+                    self.reconnects = 0;
+                    return Ok(read);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => return Err(err),
+                Err(err) if self.reconnects >= MAX_RETRIES => {
+                    warn!(url = %self.url, offset = self.offset, "Giving up on download: {err}");
+                    return Err(err);
+                }
+                Err(err) => {
+                    warn!(url = %self.url, offset = self.offset, "Download interrupted: {err}");
+                    self.reconnects += 1;
+                    self.response = connect_with_retries(&self.client, &self.url, self.offset)
+                        .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{err:#}")))?;
+                }
+            }
+        }
+    }
+}
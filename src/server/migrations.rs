@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::{
+    data::{ErdosLink, User},
+    util::DEFAULT_ROOT,
+};
+
+/// `User` as stored before schema versioning and roots, all links are to `DEFAULT_ROOT`.
+#[derive(Deserialize)]
+pub struct UserV0 {
+    id: String,
+    erdos_links: Vec<ErdosLink>,
+}
+
+pub fn user_v0(user: UserV0) -> Option<User> {
+    Some(User {
+        id: user.id,
+        erdos_links: BTreeMap::from([(DEFAULT_ROOT.name.to_string(), user.erdos_links)]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rkyvdb::{CaseInsensitiveString, Collection, Database};
+    use serde::{Deserialize, Serialize};
+
+    use crate::data::{ErdosLink, PlayerInfo, Termination, TimeControl, TimeControlType, User};
+
+    /// `User` as stored by servers without schema versions.
+    #[derive(Serialize, Deserialize)]
+    struct BaselineUser {
+        id: String,
+        erdos_links: Vec<ErdosLink>,
+    }
+
+    impl Collection for BaselineUser {
+        type KeyType = CaseInsensitiveString;
+        const CF_NAME: &'static str = "users";
+    }
+
+    fn erdos_link(erdos_number: u32, loser_id: &str) -> ErdosLink {
+        let player_info = PlayerInfo {
+            title: "".to_string(),
+            rating: 2000,
+            rating_change: 0,
+        };
+        ErdosLink {
+            erdos_number,
+            loser_id: loser_id.to_string(),
+            time: Utc
+                .with_ymd_and_hms(2014, 1, 1, erdos_number, 0, 0)
+                .unwrap(),
+            winner_info: player_info.clone(),
+            loser_info: player_info,
+            game_id: format!("game{erdos_number}"),
+            move_count: 40,
+            time_control: TimeControl {
+                game_type: TimeControlType::Blitz,
+                main: 180,
+                increment: 0,
+            },
+            winner_is_white: true,
+            termination: Termination::Resign,
+        }
+    }
+
+    #[test]
+    fn baseline_users_get_magnus_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        let path = path.to_str().unwrap();
+        let erdos_links = vec![erdos_link(2, "alice"), erdos_link(1, "DrNykterstein")];
+        let db = Database::build()
+            .add_collection::<BaselineUser>()
+            .open(path)
+            .unwrap();
+        BaselineUser::put(
+            "Bob",
+            &BaselineUser {
+                id: "Bob".to_string(),
+                erdos_links: erdos_links.clone(),
+            },
+            &db,
+        )
+        .unwrap();
+        drop(db);
+
+        let db = crate::server::database_builder().open(path).unwrap();
+        let user = User::get("bob", &db).unwrap().unwrap();
+        assert_eq!(user.id, "Bob");
+        assert_eq!(user.erdos_links.len(), 1);
+        assert_eq!(user.erdos_links["magnus"], erdos_links);
+    }
+}
@@ -1,21 +1,173 @@
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
+use rkyvdb::Collection;
 use tracing_subscriber::{fmt::format::FmtSpan, prelude::*};
 
-use crate::data::{ServerMetadata, User};
+use crate::data::{Child, ServerMetadata, Statistics, SubtreeSize, User, Win};
+use eligibility::EligibilityRules;
+use rebuild::SwappableDatabase;
 
+mod admin;
+mod backup;
+mod descendants;
+mod dump;
+mod eligibility;
 mod http;
+mod http_reader;
+mod migrations;
 mod process_archive;
+mod rebuild;
+mod replica;
+mod statistics;
+mod winning_path;
+
+#[derive(Parser)]
+pub struct Args {
+    /// Database directory.
+    #[arg(long, default_value = "db")]
+    db: PathBuf,
+    /// Replay all archives into a fresh database and swap it in once done, serving the current
+    /// one meanwhile. An interrupted rebuild is resumed.
+    #[arg(long)]
+    rebuild: bool,
+    /// Ingest archives from a local `.pgn` / `.pgn.zst` file or a directory of monthly dumps
+    /// instead of the Lichess database.
+    #[arg(long)]
+    archives: Option<PathBuf>,
+    /// Append games skipped as malformed to this file for later inspection.
+    #[arg(long)]
+    quarantine: Option<PathBuf>,
+    /// TOML file with game eligibility rules, see `EligibilityRules`.
+    #[arg(long)]
+    rules: Option<PathBuf>,
+    /// Store every eligible win, required for winning paths between arbitrary players. Winning
+    /// paths are only served once all archives were processed with it, so enabling it on an
+    /// existing database requires `--rebuild`.
+    #[arg(long)]
+    record_wins: bool,
+    /// Report which stored collections would be migrated to a new schema version and exit.
+    #[arg(long)]
+    dry_run_migrations: bool,
+    /// Back up the database to this directory after each processed archive, except while
+    /// rebuilding, and on `POST /backup/<name>` to the admin port.
+    #[arg(long)]
+    backups: Option<PathBuf>,
+    /// Number of most recent backups taken after an archive to keep.
+    #[arg(long, default_value_t = 3)]
+    keep_backups: usize,
+    /// List backups in `--backups` and exit.
+    #[arg(long, requires = "backups")]
+    list_backups: bool,
+    /// Replace the database with this backup from `--backups` before starting, e.g.
+    /// `after-2023-01` to roll back to the state after that archive.
+    #[arg(long, requires = "backups")]
+    restore_backup: Option<String>,
+    /// Write every collection to this file, e.g. to hand the computed numbers to other teams,
+    /// and exit. Can run next to a server using the same database.
+    #[arg(long)]
+    export: Option<PathBuf>,
+    /// Create `--db` from a file written by `--export` and exit.
+    #[arg(long, conflicts_with = "export")]
+    import: Option<PathBuf>,
+    /// Format of `--export` and `--import` files.
+    #[arg(long, value_enum, default_value_t = dump::Format::Json)]
+    dump_format: dump::Format,
+    /// Only serve queries from a secondary instance of `--db` which keeps its own files in this
+    /// directory, following a server ingesting into `--db`.
+    #[arg(long, conflicts_with_all = ["rebuild", "restore_backup"])]
+    replica: Option<PathBuf>,
+    /// Localhost port serving the site and API.
+    #[arg(long, default_value_t = 3001)]
+    http_port: u16,
+    /// Localhost port serving Prometheus metrics.
+    #[arg(long, default_value_t = 4001)]
+    metrics_port: u16,
+    /// Localhost port for maintenance requests like `POST /compact/<collection>`.
+    #[arg(long, conflicts_with = "replica")]
+    admin_port: Option<u16>,
+}
 
 fn register_metrics() {
-    use metrics::register_counter;
+    use metrics::{register_counter, register_gauge};
     register_counter!("games_processed");
     register_counter!("games_skipped");
     register_counter!("erdos_updated");
+    register_gauge!("archives_pending");
+}
+
+fn database_builder() -> rkyvdb::DatabaseBuilder {
+    rkyvdb::Database::build()
+        .add_collection::<User>()
+        .add_migration(0, migrations::user_v0)
+        .add_collection::<ServerMetadata>()
+        .add_collection::<Win>()
+        .add_collection::<Child>()
+        .add_collection::<SubtreeSize>()
+        .add_collection::<Statistics>()
+}
+
+/// Opens the database for writing, first building collections derived from `User` which
+/// weren't kept up to date when its users were ingested.
+fn open_database(path: &Path) -> Result<rkyvdb::Database> {
+    let path = path.to_str().context("Non UTF-8 DB path")?;
+    let db = database_builder().open(path)?;
+    let metadata = ServerMetadata::get((), &db)?.unwrap_or_default();
+    if !metadata.descendants_built || !metadata.statistics_built {
+        if !metadata.descendants_built {
+            descendants::build(&db)?;
+        }
+        if !metadata.statistics_built {
+            statistics::build(&db)?;
+        }
+        ServerMetadata::put(
+            (),
+            &ServerMetadata {
+                descendants_built: true,
+                statistics_built: true,
+                ..metadata
+            },
+            &db,
+        )?;
+    }
+    Ok(db)
 }
 
-pub async fn serve() -> Result<()> {
+fn dry_run_migrations(path: &Path) -> Result<()> {
+    let path = path.to_str().context("Non UTF-8 DB path")?;
+    let reports = database_builder().dry_run(path)?;
+    if reports.is_empty() {
+        println!("All collections are up to date");
+    }
+    for report in reports {
+        println!(
+            "{}: version {} -> {}, {} of {} records would change",
+            report.collection,
+            report.from_version,
+            report.to_version,
+            report.changed,
+            report.records
+        );
+    }
+    Ok(())
+}
+
+pub async fn serve(args: Args) -> Result<()> {
+    if args.dry_run_migrations {
+        return dry_run_migrations(&args.db);
+    }
+    if let (true, Some(dir)) = (args.list_backups, &args.backups) {
+        return backup::list(dir);
+    }
+    if let Some(path) = &args.export {
+        return dump::export(&args.db, path, args.dump_format);
+    }
+    if let Some(path) = &args.import {
+        return dump::import(&args.db, path, args.dump_format);
+    }
     let tracer = opentelemetry_otlp::new_pipeline()
         .tracing()
         .with_exporter(
@@ -40,23 +192,76 @@ pub async fn serve() -> Result<()> {
         .with(tracing_opentelemetry::layer().with_tracer(tracer))
         .try_init()?;
     metrics_exporter_prometheus::PrometheusBuilder::new()
-        .with_http_listener(([127, 0, 0, 1], 4001))
+        .with_http_listener(([127, 0, 0, 1], args.metrics_port))
         .add_global_label("service.name", "chess_erdos")
         .add_global_label("service.version", env!("CARGO_PKG_VERSION"))
         .install()?;
     register_metrics();
 
-    let db = rkyvdb::Database::build()
-        .add_collection::<User>()
-        .add_collection::<ServerMetadata>()
-        .open("db")?;
-
-    let result = tokio::select! {
-      v = http::serve(&db) => v,
-      v = process_archive::process_new_archives_task(&db) => v,
+    let result = match &args.replica {
+        Some(secondary_path) => replica::serve(&args.db, secondary_path, args.http_port).await,
+        None => serve_primary(args).await,
     };
 
     opentelemetry::global::shutdown_tracer_provider();
 
     result
 }
+
+/// Blocking DB calls of HTTP handlers running at once, see `SwappableDatabase`. Further
+/// requests wait for a slot without holding up executor threads.
+const MAX_BLOCKING_QUERIES: usize = 32;
+
+/// Serves queries while ingesting new archives.
+async fn serve_primary(args: Args) -> Result<()> {
+    rebuild::link_plain_directory(&args.db)?;
+    if let (Some(dir), Some(name)) = (&args.backups, &args.restore_backup) {
+        backup::restore(dir, name, &args.db)?;
+    }
+    rebuild::remove_stale_generations(&args.db)?;
+    let db = open_database(&args.db)?;
+    let served_db = SwappableDatabase::new(db.clone());
+
+    let ingest_options = process_archive::IngestOptions {
+        source: match args.archives {
+            Some(path) => process_archive::ArchiveSource::Local(path),
+            None => process_archive::ArchiveSource::Lichess,
+        },
+        quarantine: args.quarantine,
+        rules: args
+            .rules
+            .as_deref()
+            .map_or_else(|| Ok(EligibilityRules::default()), EligibilityRules::load)?,
+        record_wins: args.record_wins,
+        backups: args.backups.map(|dir| backup::BackupOptions {
+            dir,
+            keep: args.keep_backups,
+        }),
+    };
+
+    let (db_path, rebuild_db, http_port, admin_port) =
+        (&args.db, args.rebuild, args.http_port, args.admin_port);
+    let ingest = async {
+        let db = if rebuild_db {
+            drop(db);
+            rebuild::rebuild(&served_db, db_path, &ingest_options).await?
+        } else {
+            db
+        };
+        process_archive::process_new_archives_task(&db, &ingest_options).await
+    };
+
+    let admin = async {
+        match admin_port {
+            Some(port) => admin::serve(&served_db, port, ingest_options.backups.clone()).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+      v = http::serve(&served_db, http_port) => v,
+      v = ingest => v,
+      v = admin => v,
+      v = admin::export_db_metrics_task(served_db.clone()) => v,
+    }
+}
@@ -1,77 +1,213 @@
-use std::process::{Command, Stdio};
-use std::{collections::HashMap, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, TimeZone, Utc};
-use metrics::increment_counter;
+use metrics::{gauge, increment_counter};
 use pgn_reader::{RawHeader, SanPlus, Skip, Visitor};
 use reqwest::get;
-use rkyvdb::{Collection, Database};
+use rkyvdb::{Collection, Database, WriteBatch};
 use shakmaty::san::Suffix;
 use tokio::{task::spawn_blocking, time::sleep};
-use tracing::info;
+use tracing::{info, warn};
 
+use super::{
+    backup::{self, BackupOptions},
+    descendants::reparent,
+    eligibility::EligibilityRules,
+    http_reader::HttpReader,
+    statistics::{self, StatisticsDelta},
+};
 use crate::{
     data::{
-        ErdosLink, PlayerInfo, ServerMetadata, Termination, TimeControl, TimeControlType, User,
+        ArchiveCheckpoint, ErdosLink, PlayerInfo, ServerMetadata, Termination, TimeControl,
+        TimeControlType, User, Win,
     },
-    util::ERDOS_ID,
+    util::{Root, ROOTS},
 };
 
 const LICHESS_DB_LIST: &str = "https://database.lichess.org/standard/list.txt";
+const CHECKPOINT_INTERVAL: u64 = 10_000;
 pub const ERDOS_NUMBER_INF: u32 = u32::MAX - 1;
 
-fn user_to_erdos_number(user: &User) -> u32 {
-    if user.id == ERDOS_ID {
+#[derive(Clone)]
+pub enum ArchiveSource {
+    Lichess,
+    Local(PathBuf),
+}
+
+#[derive(Clone)]
+pub struct IngestOptions {
+    pub source: ArchiveSource,
+    /// File to append malformed games to, as PGN annotated with the skip reason.
+    pub quarantine: Option<PathBuf>,
+    pub rules: EligibilityRules,
+    /// Store every eligible win to answer winning path queries.
+    pub record_wins: bool,
+    pub backups: Option<BackupOptions>,
+}
+
+/// Extracts the `YYYY-MM` month from an archive URL or path like
+/// `.../lichess_db_standard_rated_2013-01.pgn.zst`, or `None` if it doesn't end with one.
+pub fn archive_month(archive: &str) -> Option<&str> {
+    let stem = archive.strip_suffix(".zst").unwrap_or(archive);
+    let stem = stem.strip_suffix(".pgn").unwrap_or(stem);
+    let month = stem.get(stem.len().checked_sub(7)?..)?;
+    let is_month = month.bytes().enumerate().all(|(index, byte)| match index {
+        4 => byte == b'-',
+        _ => byte.is_ascii_digit(),
+    });
+    is_month.then_some(month)
+}
+
+fn user_to_erdos_number(user: &User, root: &Root) -> u32 {
+    if user.id == root.id {
         0
     } else {
         user.erdos_links
-            .last()
+            .get(root.name)
+            .and_then(|erdos_links| erdos_links.last())
             .map(|link| link.erdos_number)
             .unwrap_or(ERDOS_NUMBER_INF)
     }
 }
 
-fn user_to_erdos_number_at(user: &User, time: DateTime<Utc>) -> u32 {
-    if user.id == ERDOS_ID {
+fn user_to_erdos_number_at(user: &User, root: &Root, time: DateTime<Utc>) -> u32 {
+    if user.id == root.id {
         0
     } else {
         user.erdos_links
-            .iter()
-            .filter(|erdos_link| erdos_link.time < time)
-            .last()
+            .get(root.name)
+            .and_then(|erdos_links| {
+                erdos_links
+                    .iter()
+                    .filter(|erdos_link| erdos_link.time < time)
+                    .last()
+            })
             .map(|erdos_link| erdos_link.erdos_number)
             .unwrap_or(ERDOS_NUMBER_INF)
     }
 }
 
+/// Latest numbers of the user, indexed like `ROOTS`.
+fn user_to_erdos_numbers(user: &User) -> Vec<u32> {
+    ROOTS
+        .iter()
+        .map(|root| user_to_erdos_number(user, root))
+        .collect()
+}
+
+/// Reason for skipping a game whose headers don't follow the Lichess database format.
+#[derive(Debug)]
+enum Malformed {
+    DuplicateHeader(&'static str),
+    MissingHeaders,
+    InvalidValue(&'static str, String),
+}
+
+impl Malformed {
+    fn reason(&self) -> String {
+        match self {
+            Malformed::DuplicateHeader(header) => format!("malformed: duplicate {header}"),
+            Malformed::MissingHeaders => "malformed: missing headers".to_string(),
+            Malformed::InvalidValue(header, _) => format!("malformed: invalid {header}"),
+        }
+    }
+}
+
+impl fmt::Display for Malformed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Malformed::DuplicateHeader(header) => write!(f, "duplicate {header} header"),
+            Malformed::MissingHeaders => write!(f, "missing required headers"),
+            Malformed::InvalidValue(header, value) => write!(f, "invalid {header}: {value:?}"),
+        }
+    }
+}
+
+fn decode_value<'v>(
+    header: &'static str,
+    value: &RawHeader<'v>,
+) -> Result<Cow<'v, str>, Malformed> {
+    value
+        .decode_utf8()
+        .map_err(|_| Malformed::InvalidValue(header, value.decode_utf8_lossy().to_string()))
+}
+
+fn parse_value<T: FromStr>(header: &'static str, value: &str) -> Result<T, Malformed> {
+    value
+        .parse()
+        .map_err(|_| Malformed::InvalidValue(header, value.to_string()))
+}
+
 #[derive(Clone)]
 struct ColorInfo {
     id: String,
-    erdos_number: u32,
+    erdos_numbers: Vec<u32>,
     player_info: PlayerInfo,
 }
 
+/// Applies games of a single archive to the DB. Everything carried between games besides
+/// `games_read` is either reset in `begin_game` or a cache of the DB, so a checkpoint only
+/// needs the number of games read: replaying from it skips that many games untouched.
 struct GameParser<'a> {
-    db: &'a Database,
+    /// Pending writes, committed together with each checkpoint.
+    batch: WriteBatch,
+    options: &'a IngestOptions,
+    archive: String,
+    games_read: u64,
+    resume_from: u64,
+    quarantine: Option<BufWriter<File>>,
+    /// Quarantined games since the previous checkpoint, written with it so that replayed games
+    /// aren't quarantined twice.
+    quarantined: Vec<u8>,
     erdos_link: ErdosLink,
     skip: bool,
+    /// Set instead of `skip` for games which are only needed for the wins graph.
+    no_improvement: bool,
+    malformed: Option<Malformed>,
+    raw_headers: Vec<(Vec<u8>, Vec<u8>)>,
+    raw_moves: Vec<String>,
     fields_bitset: u32,
     date: chrono::NaiveDate,
     time: chrono::NaiveTime,
     white: ColorInfo,
     black: ColorInfo,
     user_id: String,
-    users_cache: HashMap<String, u32>,
+    users_cache: HashMap<String, Vec<u32>>,
+    /// Not yet stored changes of the Erdos number distribution.
+    statistics_delta: StatisticsDelta,
+    /// First error while applying games, after which the rest are skipped. Returned by
+    /// `process_archive` once reading stops.
+    error: Option<anyhow::Error>,
 }
 
 impl<'a> GameParser<'a> {
-    fn new(db: &'a Database) -> Self {
+    fn new(
+        db: &Database,
+        options: &'a IngestOptions,
+        archive: &str,
+        resume_from: u64,
+        quarantine: Option<BufWriter<File>>,
+    ) -> Self {
         let mut users_cache = HashMap::new();
-        users_cache.insert("?".to_string(), ERDOS_NUMBER_INF);
-        users_cache.insert(ERDOS_ID.to_string(), 0);
+        users_cache.insert("?".to_string(), vec![ERDOS_NUMBER_INF; ROOTS.len()]);
         GameParser {
-            db,
+            batch: db.batch(),
+            options,
+            archive: archive.to_string(),
+            games_read: 0,
+            resume_from,
+            quarantine,
+            quarantined: vec![],
             erdos_link: ErdosLink {
                 erdos_number: 0,
                 loser_id: "".to_string(),
@@ -99,12 +235,16 @@ impl<'a> GameParser<'a> {
                 termination: Termination::Checkmate,
             },
             skip: false,
+            no_improvement: false,
+            malformed: None,
+            raw_headers: vec![],
+            raw_moves: vec![],
             fields_bitset: 0,
             date: chrono::NaiveDate::from_num_days_from_ce(0),
             time: chrono::NaiveTime::from_num_seconds_from_midnight(0, 0),
             white: ColorInfo {
                 id: "".to_string(),
-                erdos_number: 0,
+                erdos_numbers: vec![],
                 player_info: PlayerInfo {
                     title: "".to_string(),
                     rating: 0,
@@ -113,7 +253,7 @@ impl<'a> GameParser<'a> {
             },
             black: ColorInfo {
                 id: "".to_string(),
-                erdos_number: 0,
+                erdos_numbers: vec![],
                 player_info: PlayerInfo {
                     title: "".to_string(),
                     rating: 0,
@@ -122,69 +262,193 @@ impl<'a> GameParser<'a> {
             },
             user_id: String::new(),
             users_cache,
+            statistics_delta: statistics::new_delta(),
+            error: None,
         }
     }
-    fn get_latest_erdos_number(&mut self, id: &str) -> Result<u32> {
-        if let Some(erdos_number) = self.users_cache.get(id) {
-            Ok(*erdos_number)
-        } else if let Some(user) = User::get(id, self.db)? {
-            let erdos_number = user_to_erdos_number(&user);
-            self.users_cache.insert(id.to_string(), erdos_number);
-            Ok(erdos_number)
+
+    fn fail(&mut self, err: anyhow::Error) {
+        self.error.get_or_insert(err);
+        self.skip = true;
+    }
+    fn get_latest_erdos_numbers(&mut self, id: &str) -> Result<Vec<u32>> {
+        if let Some(erdos_numbers) = self.users_cache.get(id) {
+            Ok(erdos_numbers.clone())
+        } else if let Some(user) = User::get(id, &self.batch)? {
+            let erdos_numbers = user_to_erdos_numbers(&user);
+            self.users_cache
+                .insert(id.to_string(), erdos_numbers.clone());
+            Ok(erdos_numbers)
         } else {
-            User::modify(id, self.db, |_| {
-                Some(User {
-                    id: id.to_string(),
-                    erdos_links: vec![],
-                })
-            })?;
-            self.users_cache.insert(id.to_string(), ERDOS_NUMBER_INF);
-            Ok(ERDOS_NUMBER_INF)
+            let user = User {
+                id: id.to_string(),
+                erdos_links: BTreeMap::new(),
+            };
+            let erdos_numbers = user_to_erdos_numbers(&user);
+            User::modify(id, &self.batch, |_| Some(user))?;
+            self.users_cache
+                .insert(id.to_string(), erdos_numbers.clone());
+            Ok(erdos_numbers)
         }
     }
-}
 
-impl<'a> Visitor for GameParser<'a> {
-    type Result = ();
+    /// Commits all writes since the previous checkpoint, so that games after it can be replayed
+    /// against exactly the state they were first processed with. The quarantine file is written
+    /// first: stopping right between the two is the only way to quarantine a game twice.
+    fn save_checkpoint(&mut self) -> Result<()> {
+        if let Some(quarantine) = self.quarantine.as_mut() {
+            quarantine.write_all(&self.quarantined)?;
+            quarantine.flush()?;
+            self.quarantined.clear();
+        }
+        statistics::apply_delta(&self.statistics_delta, &self.batch)?;
+        self.statistics_delta = statistics::new_delta();
+        ServerMetadata::modify((), &self.batch, |metadata| {
+            Some(ServerMetadata {
+                checkpoint: Some(ArchiveCheckpoint {
+                    archive: self.archive.clone(),
+                    games_processed: self.games_read,
+                }),
+                ..metadata.unwrap_or_default()
+            })
+        })?;
+        self.batch.commit()?;
+        Ok(())
+    }
 
-    fn begin_game(&mut self) {
-        self.skip = false;
-        self.fields_bitset = 0;
-        self.white.player_info.title = "".to_string();
-        self.black.player_info.title = "".to_string();
-        self.erdos_link.move_count = 0;
-        self.erdos_link.game_id = "".to_string();
+    fn apply_game(&mut self) -> Result<()> {
+        if !self.skip {
+            if self.erdos_link.move_count < self.options.rules.min_plies {
+                increment_counter!("games_skipped", "reason" => "short");
+                return Ok(());
+            }
+            if !self
+                .options
+                .rules
+                .terminations
+                .contains(&self.erdos_link.termination)
+            {
+                increment_counter!("games_skipped", "reason" => format!("termination: {:?}", self.erdos_link.termination));
+                return Ok(());
+            }
+            if self.options.record_wins {
+                self.record_win()?;
+            }
+            if self.no_improvement {
+                return Ok(());
+            }
+            let winner = User::get(&self.user_id, &self.batch)?
+                .context("User should be in DB at this point")?;
+            let loser = User::get(&self.erdos_link.loser_id, &self.batch)?
+                .context("User should be in DB at this point")?;
+            let mut new_links = vec![];
+            for (root_index, root) in ROOTS.iter().enumerate() {
+                if root.start() > self.erdos_link.time {
+                    continue;
+                }
+                let loser_erdos_number =
+                    user_to_erdos_number_at(&loser, root, self.erdos_link.time);
+                let winner_erdos_number = user_to_erdos_number(&winner, root);
+                if winner_erdos_number > loser_erdos_number + 1 {
+                    increment_counter!(
+                      "erdos_updated",
+                      "root" => root.name,
+                      "new" => format!("{}", loser_erdos_number + 1),
+                      "old" => format!("{}", winner_erdos_number)
+                    );
+                    new_links.push((
+                        root_index,
+                        ErdosLink {
+                            erdos_number: loser_erdos_number + 1,
+                            ..self.erdos_link.clone()
+                        },
+                    ));
+                }
+            }
+            if new_links.is_empty() {
+                increment_counter!("games_skipped", "reason" => "erdos: slow");
+                return Ok(());
+            }
+            User::modify(&self.user_id, &self.batch, |user| {
+                let mut user = user.expect("User should be in DB at this point");
+                for (root_index, erdos_link) in &new_links {
+                    user.erdos_links
+                        .entry(ROOTS[*root_index].name.to_string())
+                        .or_default()
+                        .push(erdos_link.clone());
+                }
+                Some(user)
+            })?;
+            for (root_index, erdos_link) in &new_links {
+                let root = &ROOTS[*root_index];
+                let old_parent = winner
+                    .erdos_links
+                    .get(root.name)
+                    .and_then(|erdos_links| erdos_links.last())
+                    .map(|erdos_link| erdos_link.loser_id.as_str());
+                reparent(
+                    root,
+                    &self.user_id,
+                    old_parent,
+                    &erdos_link.loser_id,
+                    &self.batch,
+                )?;
+            }
+            let cached_erdos_numbers = self.users_cache.get_mut(&self.user_id).unwrap();
+            for (root_index, erdos_link) in new_links {
+                let old_erdos_number = cached_erdos_numbers[root_index];
+                let delta = &mut self.statistics_delta[root_index];
+                if old_erdos_number != ERDOS_NUMBER_INF {
+                    *delta.entry(old_erdos_number).or_default() -= 1;
+                }
+                *delta.entry(erdos_link.erdos_number).or_default() += 1;
+                cached_erdos_numbers[root_index] = erdos_link.erdos_number;
+            }
+        }
+        Ok(())
     }
 
-    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
-        if self.skip {
-            return;
+    fn take_field(&mut self, bit: u32, name: &'static str) -> Result<(), Malformed> {
+        if self.fields_bitset & 1 << bit != 0 {
+            return Err(Malformed::DuplicateHeader(name));
         }
+        self.fields_bitset |= 1 << bit;
+        Ok(())
+    }
+
+    fn parse_header(&mut self, key: &[u8], value: RawHeader<'_>) -> Result<(), Malformed> {
         match key {
             b"Event" => {
-                assert!(self.fields_bitset & 1 << 0 == 0);
-                self.fields_bitset |= 1 << 0;
+                self.take_field(0, "Event")?;
                 let event = value.decode();
                 let without_rated = if let Some(without_rated) = event.strip_prefix(b"Rated ") {
                     without_rated
                 } else {
                     increment_counter!("games_skipped", "reason" => "unrated");
                     self.skip = true;
-                    return;
+                    return Ok(());
                 };
-                if without_rated.starts_with(b"Blitz ") {
-                    self.erdos_link.time_control.game_type = TimeControlType::Blitz;
+                let game_type = if without_rated.starts_with(b"Blitz ") {
+                    Some(TimeControlType::Blitz)
                 } else if without_rated.starts_with(b"Rapid ") {
-                    self.erdos_link.time_control.game_type = TimeControlType::Rapid;
+                    Some(TimeControlType::Rapid)
                 } else if without_rated.starts_with(b"Classical ") {
-                    self.erdos_link.time_control.game_type = TimeControlType::Classical;
+                    Some(TimeControlType::Classical)
+                } else {
+                    None
+                };
+                if let Some(game_type) = game_type {
+                    if !self.options.rules.time_controls.contains(&game_type) {
+                        increment_counter!("games_skipped", "reason" => format!("timecontrol: {game_type:?}"));
+                        self.skip = true;
+                    }
+                    self.erdos_link.time_control.game_type = game_type;
                 } else {
                     increment_counter!(
                         "games_skipped",
                         "reason" => format!(
                             "timecontrol: {}",
-                            std::str::from_utf8(without_rated)
-                                .unwrap()
+                            String::from_utf8_lossy(without_rated)
                                 .split_ascii_whitespace()
                                 .take(2)
                                 .collect::<Vec<_>>()
@@ -195,91 +459,101 @@ impl<'a> Visitor for GameParser<'a> {
                 }
             }
             b"Site" => {
-                assert!(self.fields_bitset & 1 << 1 == 0);
-                self.fields_bitset |= 1 << 1;
-                self.erdos_link.game_id = value
-                    .decode_utf8()
-                    .unwrap()
+                self.take_field(1, "Site")?;
+                let site = decode_value("Site", &value)?;
+                self.erdos_link.game_id = site
                     .strip_prefix("https://lichess.org/")
-                    .unwrap()
+                    .ok_or_else(|| Malformed::InvalidValue("Site", site.to_string()))?
                     .to_string();
             }
             b"White" => {
-                assert!(self.fields_bitset & 1 << 2 == 0);
-                self.fields_bitset |= 1 << 2;
-                let id = value.decode_utf8().unwrap().to_string();
+                self.take_field(2, "White")?;
+                let id = decode_value("White", &value)?.to_string();
                 if id == "?" {
                     increment_counter!("games_skipped", "reason" => "unregistered: white");
                     self.skip = true;
                 } else {
-                    self.white.erdos_number = self.get_latest_erdos_number(&id).unwrap();
+                    match self.get_latest_erdos_numbers(&id) {
+                        Ok(erdos_numbers) => self.white.erdos_numbers = erdos_numbers,
+                        Err(err) => {
+                            self.fail(err);
+                            return Ok(());
+                        }
+                    }
                     self.white.id = id;
                 }
             }
             b"WhiteTitle" => {
-                assert!(self.fields_bitset & 1 << 3 == 0);
-                self.fields_bitset |= 1 << 3;
-                self.white.player_info.title = value.decode_utf8().unwrap().to_string();
+                self.take_field(3, "WhiteTitle")?;
+                self.white.player_info.title = decode_value("WhiteTitle", &value)?.to_string();
             }
             b"WhiteElo" => {
-                assert!(self.fields_bitset & 1 << 4 == 0);
-                self.fields_bitset |= 1 << 4;
-                let rating_str = value.decode_utf8().unwrap();
+                self.take_field(4, "WhiteElo")?;
+                let rating_str = decode_value("WhiteElo", &value)?;
                 if rating_str == "?" {
                     increment_counter!("games_skipped", "reason" => "unregistered: white no elo");
                     self.skip = true;
-                    return;
+                    return Ok(());
                 }
-                self.white.player_info.rating = rating_str.parse().unwrap();
+                self.white.player_info.rating = parse_value("WhiteElo", &rating_str)?;
             }
             b"WhiteRatingDiff" => {
-                assert!(self.fields_bitset & 1 << 5 == 0);
-                self.fields_bitset |= 1 << 5;
+                self.take_field(5, "WhiteRatingDiff")?;
+                let rating_change = decode_value("WhiteRatingDiff", &value)?;
                 self.white.player_info.rating_change =
-                    value.decode_utf8().unwrap().parse().unwrap();
+                    parse_value("WhiteRatingDiff", &rating_change)?;
             }
             b"Black" => {
-                assert!(self.fields_bitset & 1 << 6 == 0);
-                self.fields_bitset |= 1 << 6;
-                let id = value.decode_utf8().unwrap().to_string();
+                self.take_field(6, "Black")?;
+                let id = decode_value("Black", &value)?.to_string();
                 if id == "?" {
                     increment_counter!("games_skipped", "reason" => "unregistered: black");
                     self.skip = true;
                 } else {
-                    self.black.erdos_number = self.get_latest_erdos_number(&id).unwrap();
+                    if self.fields_bitset & 1 << 2 == 0 {
+                        return Err(Malformed::MissingHeaders);
+                    }
+                    match self.get_latest_erdos_numbers(&id) {
+                        Ok(erdos_numbers) => self.black.erdos_numbers = erdos_numbers,
+                        Err(err) => {
+                            self.fail(err);
+                            return Ok(());
+                        }
+                    }
                     self.black.id = id;
-                    assert!(self.fields_bitset & 1 << 2 != 0);
-                    if self.white.erdos_number.abs_diff(self.black.erdos_number) <= 1 {
-                        increment_counter!("games_skipped", "reason" => "erdos: fast");
-                        self.skip = true;
+                    if self
+                        .white
+                        .erdos_numbers
+                        .iter()
+                        .zip(&self.black.erdos_numbers)
+                        .all(|(white, black)| white.abs_diff(*black) <= 1)
+                    {
+                        self.skip_erdos("erdos: fast");
                     }
                 }
             }
             b"BlackTitle" => {
-                assert!(self.fields_bitset & 1 << 7 == 0);
-                self.fields_bitset |= 1 << 7;
-                self.black.player_info.title = value.decode_utf8().unwrap().to_string();
+                self.take_field(7, "BlackTitle")?;
+                self.black.player_info.title = decode_value("BlackTitle", &value)?.to_string();
             }
             b"BlackElo" => {
-                assert!(self.fields_bitset & 1 << 8 == 0);
-                self.fields_bitset |= 1 << 8;
-                let rating_str = value.decode_utf8().unwrap();
+                self.take_field(8, "BlackElo")?;
+                let rating_str = decode_value("BlackElo", &value)?;
                 if rating_str == "?" {
                     increment_counter!("games_skipped", "reason" => "unregistered: black no elo");
                     self.skip = true;
-                    return;
+                    return Ok(());
                 }
-                self.black.player_info.rating = rating_str.parse().unwrap();
+                self.black.player_info.rating = parse_value("BlackElo", &rating_str)?;
             }
             b"BlackRatingDiff" => {
-                assert!(self.fields_bitset & 1 << 9 == 0);
-                self.fields_bitset |= 1 << 9;
+                self.take_field(9, "BlackRatingDiff")?;
+                let rating_change = decode_value("BlackRatingDiff", &value)?;
                 self.black.player_info.rating_change =
-                    value.decode_utf8().unwrap().parse().unwrap();
+                    parse_value("BlackRatingDiff", &rating_change)?;
             }
             b"Result" => {
-                assert!(self.fields_bitset & 1 << 10 == 0);
-                self.fields_bitset |= 1 << 10;
+                self.take_field(10, "Result")?;
                 match value.decode().as_ref() {
                     b"1-0" => {
                         self.erdos_link.winner_is_white = true;
@@ -292,88 +566,209 @@ impl<'a> Visitor for GameParser<'a> {
                         self.skip = true;
                     }
                     unknown_result => {
-                        increment_counter!("games_skipped", "reason" => format!("result: {}", std::str::from_utf8(unknown_result).unwrap()));
+                        increment_counter!("games_skipped", "reason" => format!("result: {}", String::from_utf8_lossy(unknown_result)));
                         self.skip = true;
                     }
                 }
             }
             b"UTCDate" => {
-                assert!(self.fields_bitset & 1 << 11 == 0);
-                self.fields_bitset |= 1 << 11;
-                let date_str = value.decode_utf8().unwrap();
+                self.take_field(11, "UTCDate")?;
+                let date_str = decode_value("UTCDate", &value)?;
                 self.date = chrono::NaiveDate::parse_from_str(&date_str, "%Y.%m.%d")
-                    .unwrap_or_else(|_| panic!("Failed to parse date: {date_str}"));
+                    .map_err(|_| Malformed::InvalidValue("UTCDate", date_str.to_string()))?;
             }
             b"UTCTime" => {
-                assert!(self.fields_bitset & 1 << 12 == 0);
-                self.fields_bitset |= 1 << 12;
-                let time_str = value.decode_utf8().unwrap();
+                self.take_field(12, "UTCTime")?;
+                let time_str = decode_value("UTCTime", &value)?;
                 self.time = chrono::NaiveTime::parse_from_str(&time_str, "%H:%M:%S")
-                    .unwrap_or_else(|_| panic!("Failed to parse time: {time_str}"));
+                    .map_err(|_| Malformed::InvalidValue("UTCTime", time_str.to_string()))?;
             }
             b"TimeControl" => {
-                assert!(self.fields_bitset & 1 << 13 == 0);
-                self.fields_bitset |= 1 << 13;
-                let time_control = value.decode_utf8().unwrap();
-                let (main_str, increment_str) = time_control
-                    .split_once('+')
-                    .expect("Unexpected TimeControl format");
-                self.erdos_link.time_control.main = main_str.parse().unwrap();
-                self.erdos_link.time_control.increment = increment_str.parse().unwrap();
+                self.take_field(13, "TimeControl")?;
+                let time_control = decode_value("TimeControl", &value)?;
+                let (main_str, increment_str) = time_control.split_once('+').ok_or_else(|| {
+                    Malformed::InvalidValue("TimeControl", time_control.to_string())
+                })?;
+                self.erdos_link.time_control.main = parse_value("TimeControl", main_str)?;
+                self.erdos_link.time_control.increment = parse_value("TimeControl", increment_str)?;
             }
             b"Termination" => {
-                assert!(self.fields_bitset & 1 << 14 == 0);
-                self.fields_bitset |= 1 << 14;
+                self.take_field(14, "Termination")?;
+                let terminations = &self.options.rules.terminations;
                 match value.decode().as_ref() {
-                    b"Normal" => {
+                    b"Normal"
+                        if terminations.contains(&Termination::Resign)
+                            || terminations.contains(&Termination::Checkmate) =>
+                    {
                         self.erdos_link.termination = Termination::Resign;
                     }
-                    b"Time forfeit" => {
+                    b"Time forfeit" if terminations.contains(&Termination::Time) => {
                         self.erdos_link.termination = Termination::Time;
                     }
                     unknown_termination => {
-                        increment_counter!("games_skipped", "reason" => format!("termination: {}", std::str::from_utf8(unknown_termination).unwrap()));
+                        increment_counter!("games_skipped", "reason" => format!("termination: {}", String::from_utf8_lossy(unknown_termination)));
                         self.skip = true;
                     }
                 }
             }
             _ => {}
         }
+        Ok(())
+    }
+
+    /// Skips a game which can't improve any number, unless it's still needed for the wins
+    /// graph. Only skipped games count towards `games_skipped`.
+    fn skip_erdos(&mut self, reason: &'static str) {
+        if self.options.record_wins {
+            self.no_improvement = true;
+        } else {
+            increment_counter!("games_skipped", "reason" => reason);
+            self.skip = true;
+        }
+    }
+
+    /// Every win is kept, as a path may need an earlier one than the latest.
+    fn record_win(&self) -> Result<()> {
+        let win = Win {
+            loser_id: self.erdos_link.loser_id.clone(),
+            time: self.erdos_link.time,
+            game_id: self.erdos_link.game_id.clone(),
+        };
+        Win::put(win.key(&self.user_id), &win, &self.batch)?;
+        Ok(())
+    }
+
+    fn skip_malformed(&mut self, malformed: Malformed) {
+        increment_counter!("games_skipped", "reason" => malformed.reason());
+        self.skip = true;
+        self.malformed = Some(malformed);
+    }
+
+    fn quarantine_game(&mut self, malformed: &Malformed) -> Result<()> {
+        if self.quarantine.is_none() {
+            return Ok(());
+        }
+        let quarantine = &mut self.quarantined;
+        for (key, value) in &self.raw_headers {
+            quarantine.write_all(b"[")?;
+            quarantine.write_all(key)?;
+            quarantine.write_all(b" \"")?;
+            quarantine.write_all(value)?;
+            quarantine.write_all(b"\"]\n")?;
+        }
+        write!(quarantine, "\n{{ {}: {malformed} }}", self.archive)?;
+        for (ply, san) in self.raw_moves.iter().enumerate() {
+            if ply % 2 == 0 {
+                write!(quarantine, " {}.", ply / 2 + 1)?;
+            }
+            write!(quarantine, " {san}")?;
+        }
+        let result = self
+            .raw_headers
+            .iter()
+            .find(|(key, _)| key == b"Result")
+            .map_or(Cow::Borrowed("*"), |(_, value)| {
+                String::from_utf8_lossy(value)
+            });
+        writeln!(quarantine, " {result}\n")?;
+        Ok(())
+    }
+}
+
+impl<'a> Visitor for GameParser<'a> {
+    type Result = ();
+
+    fn begin_game(&mut self) {
+        self.skip = self.games_read < self.resume_from || self.error.is_some();
+        self.no_improvement = false;
+        self.malformed = None;
+        self.raw_headers.clear();
+        self.raw_moves.clear();
+        self.fields_bitset = 0;
+        self.white.player_info.title = "".to_string();
+        self.black.player_info.title = "".to_string();
+        self.erdos_link.move_count = 0;
+        self.erdos_link.game_id = "".to_string();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        if self.quarantine.is_some() && (!self.skip || self.malformed.is_some()) {
+            self.raw_headers
+                .push((key.to_vec(), value.as_bytes().to_vec()));
+        }
+        if self.skip {
+            return;
+        }
+        if let Err(malformed) = self.parse_header(key, value) {
+            self.skip_malformed(malformed);
+        }
     }
 
     fn end_headers(&mut self) -> Skip {
         if !self.skip {
             self.fields_bitset |= 1 << 3 | 1 << 7;
             if self.fields_bitset != (1 << 15) - 1 {
-                assert!(
-                    self.fields_bitset | 1 << 5 | 1 << 9 == (1 << 15) - 1,
-                    "{}",
-                    self.erdos_link.game_id
-                );
-                increment_counter!("games_skipped", "reason" => "cheater: missing rating diff");
-                self.skip = true;
+                if self.fields_bitset | 1 << 5 | 1 << 9 != (1 << 15) - 1 {
+                    self.skip_malformed(Malformed::MissingHeaders);
+                } else {
+                    increment_counter!("games_skipped", "reason" => "cheater: missing rating diff");
+                    self.skip = true;
+                }
             }
+        }
+        if !self.skip {
             let (winner, loser) = if self.erdos_link.winner_is_white {
                 (self.white.clone(), self.black.clone())
             } else {
                 (self.black.clone(), self.white.clone())
             };
-            let winner_erdos = self.get_latest_erdos_number(&winner.id).unwrap();
-            let loser_erdos = self.get_latest_erdos_number(&loser.id).unwrap();
-            if winner_erdos <= loser_erdos + 1 {
-                increment_counter!("games_skipped", "reason" => "erdos: middle");
+            if let Err(reason) = self
+                .options
+                .rules
+                .check_ratings(winner.player_info.rating, loser.player_info.rating)
+            {
+                increment_counter!("games_skipped", "reason" => reason);
                 self.skip = true;
                 return Skip(true);
             }
+            self.erdos_link.time =
+                chrono::DateTime::from_utc(chrono::NaiveDateTime::new(self.date, self.time), Utc);
+            let erdos_numbers =
+                self.get_latest_erdos_numbers(&winner.id)
+                    .and_then(|winner_erdos| {
+                        Ok((winner_erdos, self.get_latest_erdos_numbers(&loser.id)?))
+                    });
+            let (winner_erdos, loser_erdos) = match erdos_numbers {
+                Ok(erdos_numbers) => erdos_numbers,
+                Err(err) => {
+                    self.fail(err);
+                    return Skip(true);
+                }
+            };
+            let may_improve = ROOTS.iter().enumerate().any(|(root_index, root)| {
+                root.start() <= self.erdos_link.time
+                    && winner_erdos[root_index] > loser_erdos[root_index] + 1
+            });
+            if !may_improve {
+                self.skip_erdos("erdos: middle");
+                if self.skip {
+                    return Skip(true);
+                }
+            }
             self.user_id = winner.id;
             self.erdos_link.loser_id = loser.id;
             self.erdos_link.winner_info = winner.player_info;
             self.erdos_link.loser_info = loser.player_info;
         }
-        Skip(self.skip)
+        // Quarantined games keep their movetext.
+        Skip(self.skip && (self.malformed.is_none() || self.quarantine.is_none()))
     }
 
     fn san(&mut self, san: SanPlus) {
+        if self.malformed.is_some() {
+            self.raw_moves.push(san.to_string());
+            return;
+        }
         self.erdos_link.move_count += 1;
         if san.suffix == Some(Suffix::Checkmate) {
             self.erdos_link.termination = Termination::Checkmate;
@@ -385,103 +780,500 @@ impl<'a> Visitor for GameParser<'a> {
     }
 
     fn end_game(&mut self) -> Self::Result {
+        self.games_read += 1;
+        if self.games_read <= self.resume_from || self.error.is_some() {
+            return;
+        }
         increment_counter!("games_processed");
-        if !self.skip {
-            if self.erdos_link.move_count < 20 {
-                increment_counter!("games_skipped", "reason" => "short");
-                return; // Skip games with less than 20 moves.
+        if let Some(malformed) = self.malformed.take() {
+            if let Err(err) = self.quarantine_game(&malformed) {
+                warn!("Failed to quarantine game: {err:#}");
             }
-            self.erdos_link.time =
-                chrono::DateTime::from_utc(chrono::NaiveDateTime::new(self.date, self.time), Utc);
-            let winner = User::get(&self.user_id, self.db)
-                .unwrap()
-                .expect("User should be in DB at this point");
-            let loser_erdos_number = if self.erdos_link.loser_id == ERDOS_ID {
-                0
-            } else {
-                user_to_erdos_number_at(
-                    &User::get(&self.erdos_link.loser_id, self.db)
-                        .unwrap()
-                        .expect("User should be in DB at this point"),
-                    self.erdos_link.time,
-                )
-            };
-            let winner_erdos_number = user_to_erdos_number(&winner);
-            if winner_erdos_number > loser_erdos_number + 1 {
-                increment_counter!(
-                  "erdos_updated",
-                  "new" => format!("{}", loser_erdos_number + 1),
-                  "old" => format!("{}", winner_erdos_number)
-                );
-                self.erdos_link.erdos_number = loser_erdos_number + 1;
-                User::modify(&self.user_id, self.db, |user| {
-                    let mut user = user.expect("User should be in DB at this point");
-                    user.erdos_links.push(self.erdos_link.clone());
-                    Some(user)
-                })
-                .unwrap();
-                *self.users_cache.get_mut(&self.user_id).unwrap() = self.erdos_link.erdos_number;
-            } else {
-                increment_counter!("games_skipped", "reason" => "erdos: slow");
+        }
+        let result = self.apply_game().and_then(|()| {
+            if self.games_read % CHECKPOINT_INTERVAL == 0 {
+                self.save_checkpoint()?;
             }
+            Ok(())
+        });
+        if let Err(err) = result {
+            self.fail(err);
         }
     }
 }
 
+fn open_archive(archive: &str) -> Result<Box<dyn Read>> {
+    let is_remote = archive.starts_with("https://") || archive.starts_with("http://");
+    let reader: Box<dyn Read> = if is_remote {
+        Box::new(HttpReader::open(archive)?)
+    } else {
+        Box::new(File::open(archive).with_context(|| format!("Failed to open {archive}"))?)
+    };
+    Ok(if archive.ends_with(".zst") {
+        let decoder =
+            zstd::stream::read::Decoder::new(reader).context("Failed to create zstd decoder")?;
+        Box::new(decoder)
+    } else {
+        reader
+    })
+}
+
 #[tracing::instrument(skip(db))]
-fn process_archive(db: &Database, url: &str) -> Result<()> {
-    let mut curl_child = Command::new("curl")
-        .arg(url)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()?;
-    let curl_output = curl_child.stdout.take().context("No curl stdout")?;
-    let mut pbzip_child = Command::new("pzstd")
-        .arg("-d")
-        .arg("-c")
-        .stdin(curl_output)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()?;
-    let pbzip_output = pbzip_child.stdout.take().context("No pbzip stdout")?;
-    let mut pgn_read = pgn_reader::BufferedReader::new(pbzip_output);
-    let mut game_parser = GameParser::new(db);
-    pgn_read.read_all(&mut game_parser)?;
-    ensure!(curl_child.wait()?.success(), "Curl failed");
-    ensure!(pbzip_child.wait()?.success(), "Pbzip failed");
+fn process_archive(db: &Database, archive: &str, options: &IngestOptions) -> Result<()> {
+    let resume_from = ServerMetadata::get((), db)?
+        .and_then(|metadata| metadata.checkpoint)
+        .filter(|checkpoint| checkpoint.archive == archive)
+        .map_or(0, |checkpoint| checkpoint.games_processed);
+    if resume_from > 0 {
+        info!(resume_from, "Resuming archive from checkpoint");
+    }
+    let mut pgn_read = pgn_reader::BufferedReader::new(open_archive(archive)?);
+    let quarantine = options
+        .quarantine
+        .as_ref()
+        .map(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map(BufWriter::new)
+                .with_context(|| format!("Failed to open quarantine file {}", path.display()))
+        })
+        .transpose()?;
+    let mut game_parser = GameParser::new(db, options, archive, resume_from, quarantine);
+    pgn_read
+        .read_all(&mut game_parser)
+        .with_context(|| format!("Failed to read archive {archive}"))?;
+    if let Some(err) = game_parser.error.take() {
+        return Err(err.context(format!("Failed to process archive {archive}")));
+    }
+    game_parser.save_checkpoint()
+}
+
+async fn list_lichess_archives() -> Result<Vec<String>> {
+    Ok(get(LICHESS_DB_LIST)
+        .await?
+        .text()
+        .await?
+        .split_ascii_whitespace()
+        .rev()
+        .map(String::from)
+        .collect())
+}
+
+fn list_local_archives(path: &Path) -> Result<Vec<String>> {
+    let paths = if path.is_dir() {
+        path.read_dir()
+            .with_context(|| format!("Failed to list {}", path.display()))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut archives = paths
+        .into_iter()
+        .map(|path| {
+            path.to_str()
+                .map(String::from)
+                .with_context(|| format!("Non UTF-8 archive path: {}", path.display()))
+        })
+        .filter(|archive| {
+            archive.as_ref().map_or(true, |archive| {
+                archive.ends_with(".pgn") || archive.ends_with(".pgn.zst")
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Archives are only processed after the last one by month, so every name needs one.
+    if let Some(archive) = archives
+        .iter()
+        .find(|archive| archive_month(archive).is_none())
+    {
+        bail!("Archive name should end with YYYY-MM.pgn or YYYY-MM.pgn.zst: {archive}");
+    }
+    archives.sort_by(|a, b| archive_month(a).cmp(&archive_month(b)).then(a.cmp(b)));
+    // Both would be processed in the same pass, counting the month's games twice.
+    if let Some(pair) = archives
+        .windows(2)
+        .find(|pair| archive_month(&pair[0]) == archive_month(&pair[1]))
+    {
+        bail!("Archives {} and {} are of the same month", pair[0], pair[1]);
+    }
+    Ok(archives)
+}
+
+/// Processes every archive newer than the last processed one.
+pub async fn process_new_archives(db: &Database, options: &IngestOptions) -> Result<()> {
+    let metadata = ServerMetadata::get((), db)?.unwrap_or_default();
+    let last_archive = metadata.last_processed_archive;
+    let wins_recorded = options.record_wins && (last_archive.is_empty() || metadata.wins_recorded);
+    let archives = match &options.source {
+        ArchiveSource::Lichess => list_lichess_archives().await?,
+        ArchiveSource::Local(path) => list_local_archives(path)?,
+    };
+    let last_month = archive_month(&last_archive);
+    let new_archives: Vec<(String, String)> = archives
+        .into_iter()
+        .filter_map(|archive| match archive_month(&archive) {
+            Some(month) if Some(month) > last_month => {
+                let month = month.to_string();
+                Some((archive, month))
+            }
+            Some(_) => None,
+            None => {
+                warn!(%archive, "Skipping archive without a YYYY-MM month");
+                None
+            }
+        })
+        .collect();
+    info!("New archives found: {}", new_archives.len());
+    for (index, (archive, month)) in new_archives.iter().enumerate() {
+        gauge!("archives_pending", (new_archives.len() - index) as f64);
+        info!(%archive, "Processing archive");
+        {
+            let db = db.clone();
+            let archive = archive.clone();
+            let options = options.clone();
+            spawn_blocking(move || process_archive(&db, &archive, &options)).await??;
+        }
+        info!(%archive, "Archive processed");
+        let mut batch = db.batch();
+        statistics::snapshot(month, &batch)?;
+        ServerMetadata::modify((), &batch, |metadata| {
+            Some(ServerMetadata {
+                last_processed_archive: archive.clone(),
+                checkpoint: None,
+                wins_recorded,
+                ..metadata.unwrap_or_default()
+            })
+        })?;
+        batch.commit()?;
+        if let Some(backups) = &options.backups {
+            let db = db.clone();
+            let backups = backups.clone();
+            let month = month.clone();
+            spawn_blocking(move || backup::create_after_archive(&db, &backups, &month)).await??;
+        }
+    }
+    gauge!("archives_pending", 0.);
     Ok(())
 }
 
-pub async fn process_new_archives_task(db: &Database) -> Result<()> {
+pub async fn process_new_archives_task(db: &Database, options: &IngestOptions) -> Result<()> {
     loop {
-        let last_archive = ServerMetadata::get((), db)
+        process_new_archives(db, options).await?;
+        sleep(Duration::from_secs(60 * 60)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        fs::{self, File},
+        io::BufWriter,
+        path::{Path, PathBuf},
+    };
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use pgn_reader::BufferedReader;
+    use rkyvdb::{Collection, Database};
+
+    use super::{
+        archive_month, list_local_archives, ArchiveSource, EligibilityRules, GameParser,
+        IngestOptions,
+    };
+    use crate::data::{ServerMetadata, Statistics, Termination, User};
+
+    const ARCHIVE: &str = "lichess_db_standard_rated_2014-01.pgn";
+    const MOVES: &str = "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 \
+                         7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7 11. Nbd2 Bb7";
+
+    fn game(id: &str, white: &str, black: &str, result: &str, hour: u32) -> String {
+        format!(
+            "[Event \"Rated Blitz game\"]\n\
+             [Site \"https://lichess.org/{id}\"]\n\
+             [White \"{white}\"]\n\
+             [Black \"{black}\"]\n\
+             [Result \"{result}\"]\n\
+             [UTCDate \"2014.01.01\"]\n\
+             [UTCTime \"{hour}:00:00\"]\n\
+             [WhiteElo \"2000\"]\n\
+             [BlackElo \"2000\"]\n\
+             [WhiteRatingDiff \"+5\"]\n\
+             [BlackRatingDiff \"-5\"]\n\
+             [TimeControl \"180+0\"]\n\
+             [Termination \"Normal\"]\n\
+             \n\
+             {MOVES} {result}\n\n"
+        )
+    }
+
+    /// alice beats the root, bob beats alice, carol beats bob. bob's first win has an invalid
+    /// `Site` and must be skipped.
+    fn fixture() -> Vec<String> {
+        vec![
+            game("game1", "DrNykterstein", "alice", "0-1", 10),
+            game("game2", "bob", "alice", "1-0", 11).replace("lichess.org", "example.com"),
+            game("game3", "bob", "alice", "1-0", 12),
+            game("game4", "carol", "bob", "1-0", 13),
+        ]
+    }
+
+    fn options() -> IngestOptions {
+        IngestOptions {
+            source: ArchiveSource::Local(PathBuf::new()),
+            quarantine: None,
+            rules: EligibilityRules::default(),
+            record_wins: false,
+            backups: None,
+        }
+    }
+
+    fn open() -> Database {
+        crate::server::database_builder().open_in_memory().unwrap()
+    }
+
+    fn resume_from(db: &Database) -> u64 {
+        ServerMetadata::get((), db)
             .unwrap()
-            .map(|x| x.last_processed_archive)
-            .unwrap_or_else(|| String::from(""));
-        let lichess_archives: Vec<String> = get(LICHESS_DB_LIST)
-            .await?
-            .text()
-            .await?
-            .split_ascii_whitespace()
-            .rev()
-            .map(String::from)
-            .skip_while(|archive| archive <= &last_archive)
-            .collect();
-        info!("New archives found: {}", lichess_archives.len());
-        for archive in lichess_archives {
-            info!(%archive, "Processing archive");
-            {
-                let db = db.clone();
-                let archive = archive.clone();
-                spawn_blocking(move || process_archive(&db, &archive)).await??;
-            }
-            info!(%archive, "Archive processed");
-            ServerMetadata::modify((), db, |_| {
-                Some(ServerMetadata {
-                    last_processed_archive: archive,
-                })
-            })?;
+            .and_then(|metadata| metadata.checkpoint)
+            .map_or(0, |checkpoint| checkpoint.games_processed)
+    }
+
+    /// Parses `games` after the last checkpoint, saving a new one at the end unless `crash`.
+    fn ingest(db: &Database, games: &[String], quarantine: Option<&Path>, crash: bool) {
+        ingest_with(db, &options(), games, quarantine, crash);
+    }
+
+    fn ingest_with(
+        db: &Database,
+        options: &IngestOptions,
+        games: &[String],
+        quarantine: Option<&Path>,
+        crash: bool,
+    ) {
+        let quarantine = quarantine.map(|path| {
+            BufWriter::new(
+                File::options()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .unwrap(),
+            )
+        });
+        let mut parser = GameParser::new(db, options, ARCHIVE, resume_from(db), quarantine);
+        BufferedReader::new_cursor(games.concat().as_bytes())
+            .read_all(&mut parser)
+            .unwrap();
+        assert!(parser.error.is_none(), "{:?}", parser.error);
+        if !crash {
+            parser.save_checkpoint().unwrap();
         }
-        sleep(Duration::from_secs(60 * 60)).await;
+    }
+
+    /// Erdos number, loser and game of each magnus link of the user.
+    fn links(db: &Database, id: &str) -> Vec<(u32, String, String)> {
+        root_links(db, "magnus", id)
+    }
+
+    fn root_links(db: &Database, root: &str, id: &str) -> Vec<(u32, String, String)> {
+        User::get(id, db)
+            .unwrap()
+            .and_then(|user| user.erdos_links.get(root).cloned())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|link| (link.erdos_number, link.loser_id, link.game_id))
+            .collect()
+    }
+
+    fn current_statistics(db: &Database) -> BTreeMap<u32, u64> {
+        Statistics::get((), db).unwrap().unwrap().roots["magnus"]
+            .current
+            .clone()
+    }
+
+    fn assert_fixture_ingested(db: &Database) {
+        let link =
+            |number: u32, loser: &str, game: &str| (number, loser.to_string(), game.to_string());
+        assert_eq!(links(db, "alice"), [link(1, "DrNykterstein", "game1")]);
+        assert_eq!(links(db, "bob"), [link(2, "alice", "game3")]);
+        assert_eq!(links(db, "carol"), [link(3, "bob", "game4")]);
+        assert_eq!(
+            current_statistics(db),
+            BTreeMap::from([(1, 1), (2, 1), (3, 1)])
+        );
+        assert_eq!(resume_from(db), 4);
+    }
+
+    #[test]
+    fn links_winners_and_skips_malformed_games() {
+        let db = open();
+        ingest(&db, &fixture(), None, false);
+        assert_fixture_ingested(&db);
+    }
+
+    /// `games_skipped` per reason counted on this thread while running `f`.
+    fn skip_reasons(f: impl FnOnce()) -> BTreeMap<String, u64> {
+        // Fails if another test installed it already, which is fine as counts are per thread.
+        let _ = DebuggingRecorder::per_thread().install();
+        f();
+        Snapshotter::current_thread_snapshot()
+            .map(|snapshot| snapshot.into_vec())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(key, ..)| key.key().name() == "games_skipped")
+            .filter_map(|(key, _, _, value)| {
+                let reason = key
+                    .key()
+                    .labels()
+                    .find(|label| label.key() == "reason")?
+                    .value()
+                    .to_string();
+                match value {
+                    DebugValue::Counter(count) => Some((reason, count)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn applies_eligibility_rules() {
+        let db = open();
+        let options = IngestOptions {
+            rules: EligibilityRules {
+                terminations: vec![Termination::Resign],
+                min_loser_rating: Some(1900),
+                ..EligibilityRules::default()
+            },
+            ..options()
+        };
+        let games = [
+            game("game1", "DrNykterstein", "alice", "0-1", 10),
+            game("game2", "bob", "alice", "1-0", 11)
+                .replace("[Termination \"Normal\"]", "[Termination \"Time forfeit\"]"),
+            game("game3", "carol", "alice", "1-0", 12)
+                .replace("[BlackElo \"2000\"]", "[BlackElo \"1800\"]"),
+        ];
+        let skipped = skip_reasons(|| ingest_with(&db, &options, &games, None, false));
+        assert_eq!(
+            skipped,
+            BTreeMap::from([
+                ("rating: loser too low".to_string(), 1),
+                ("termination: Time forfeit".to_string(), 1),
+            ])
+        );
+        assert_eq!(links(&db, "alice").len(), 1);
+        assert!(links(&db, "bob").is_empty());
+        assert!(links(&db, "carol").is_empty());
+    }
+
+    #[test]
+    fn roots_are_independent() {
+        let db = open();
+        let games: Vec<String> = [
+            game("game1", "DrNykterstein", "alice", "0-1", 10),
+            game("game2", "bob", "DingLiren", "1-0", 11),
+            game("game3", "bob", "alice", "1-0", 12),
+            game("game4", "alice", "bob", "1-0", 13),
+        ]
+        .into_iter()
+        .map(|game| game.replace("2014.01.01", "2023.05.01"))
+        .collect();
+        ingest(&db, &games, None, false);
+        let link =
+            |number: u32, loser: &str, game: &str| (number, loser.to_string(), game.to_string());
+        assert_eq!(
+            root_links(&db, "magnus", "alice"),
+            [link(1, "DrNykterstein", "game1")]
+        );
+        assert_eq!(root_links(&db, "ding", "alice"), [link(2, "bob", "game4")]);
+        assert_eq!(
+            root_links(&db, "magnus", "bob"),
+            [link(2, "alice", "game3")]
+        );
+        assert_eq!(
+            root_links(&db, "ding", "bob"),
+            [link(1, "DingLiren", "game2")]
+        );
+        // Games before a root's start don't count towards it.
+        let db = open();
+        ingest(
+            &db,
+            &[game("game2", "bob", "DingLiren", "1-0", 11)],
+            None,
+            false,
+        );
+        assert!(root_links(&db, "ding", "bob").is_empty());
+    }
+
+    #[test]
+    fn statistics_follow_improvements() {
+        let db = open();
+        let mut games = fixture();
+        ingest(&db, &games, None, false);
+        // carol improves from 3 to 2 by beating alice.
+        games.push(game("game5", "carol", "alice", "1-0", 14));
+        ingest(&db, &games, None, false);
+        assert_eq!(current_statistics(&db), BTreeMap::from([(1, 1), (2, 2)]));
+        // Beating bob again doesn't improve anyone.
+        games.push(game("game6", "carol", "bob", "1-0", 15));
+        ingest(&db, &games, None, false);
+        assert_eq!(current_statistics(&db), BTreeMap::from([(1, 1), (2, 2)]));
+    }
+
+    #[test]
+    fn local_archives_need_distinct_months() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["2023-02.pgn", "2023-01.pgn.zst", "notes.txt"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+        let archives = list_local_archives(dir.path()).unwrap();
+        let names: Vec<_> = archives
+            .iter()
+            .map(|archive| Path::new(archive).file_name().unwrap())
+            .collect();
+        assert_eq!(names, ["2023-01.pgn.zst", "2023-02.pgn"]);
+
+        File::create(dir.path().join("2023-01.pgn")).unwrap();
+        let err = list_local_archives(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("same month"), "{err:#}");
+    }
+
+    #[test]
+    fn archive_month_needs_year_and_month() {
+        assert_eq!(archive_month(ARCHIVE), Some("2014-01"));
+        assert_eq!(
+            archive_month(
+                "https://database.lichess.org/standard/lichess_db_standard_rated_2013-01.pgn.zst"
+            ),
+            Some("2013-01")
+        );
+        assert_eq!(archive_month("mygames.pgn"), None);
+        assert_eq!(archive_month("games-01.pgn.zst"), None);
+        assert_eq!(archive_month(""), None);
+    }
+
+    #[test]
+    fn replays_games_after_checkpoint() {
+        let db = open();
+        let games = fixture();
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine = dir.path().join("quarantine.pgn");
+        ingest(&db, &games[..1], Some(&quarantine), false);
+        assert_eq!(resume_from(&db), 1);
+        // Writes after the checkpoint are lost, as if the server stopped before the next one.
+        ingest(&db, &games, Some(&quarantine), true);
+        assert!(links(&db, "bob").is_empty());
+        assert_eq!(fs::read_to_string(&quarantine).unwrap(), "");
+        ingest(&db, &games, Some(&quarantine), false);
+        assert_fixture_ingested(&db);
+        let quarantined = fs::read_to_string(&quarantine).unwrap();
+        assert_eq!(
+            quarantined
+                .matches("[Site \"https://example.com/game2\"]")
+                .count(),
+            1
+        );
     }
 }
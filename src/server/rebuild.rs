@@ -0,0 +1,236 @@
+use std::{
+    ffi::OsString,
+    fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rkyvdb::{AsyncDatabase, Database};
+use tracing::{info, warn};
+
+use super::{
+    open_database,
+    process_archive::{process_new_archives, IngestOptions},
+    MAX_BLOCKING_QUERIES,
+};
+
+/// Database handle shared with the HTTP handlers, which can be pointed at a rebuilt database
+/// while the server keeps running.
+#[derive(Clone)]
+pub struct SwappableDatabase(Arc<RwLock<AsyncDatabase>>);
+
+impl SwappableDatabase {
+    pub fn new(db: Database) -> Self {
+        SwappableDatabase(Arc::new(RwLock::new(AsyncDatabase::new(
+            db,
+            MAX_BLOCKING_QUERIES,
+        ))))
+    }
+
+    pub fn get(&self) -> AsyncDatabase {
+        self.0.read().unwrap().clone()
+    }
+
+    fn swap(&self, db: Database) {
+        *self.0.write().unwrap() = AsyncDatabase::new(db, MAX_BLOCKING_QUERIES);
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Path of a new, not yet existing generation of the database at `db_path`.
+pub fn new_generation(db_path: &Path) -> PathBuf {
+    let mut timestamp = Utc::now().timestamp();
+    loop {
+        let generation = with_suffix(db_path, &format!(".{timestamp}"));
+        if !generation.exists() {
+            return generation;
+        }
+        timestamp += 1;
+    }
+}
+
+/// Link to the generation an unfinished rebuild writes to, so that it is resumed after a restart.
+fn rebuilding_link(db_path: &Path) -> PathBuf {
+    with_suffix(db_path, ".rebuilding")
+}
+
+/// Path `link` points to, if it is a symlink.
+fn link_target(link: &Path) -> Option<PathBuf> {
+    fs::read_link(link)
+        .ok()
+        .map(|target| link.with_file_name(target))
+}
+
+/// Deletes generations next to `db_path` that are neither served nor being rebuilt.
+pub fn remove_stale_generations(db_path: &Path) -> Result<()> {
+    let prefix = match db_path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => format!("{name}."),
+        None => return Ok(()),
+    };
+    let dir = match db_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let in_use = [link_target(db_path), link_target(&rebuilding_link(db_path))];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_generation = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .map_or(false, |suffix| {
+                !suffix.is_empty() && suffix.bytes().all(|b| b.is_ascii_digit())
+            });
+        let is_in_use = in_use
+            .iter()
+            .flatten()
+            .any(|target| target.file_name() == path.file_name());
+        if !is_generation || is_in_use {
+            continue;
+        }
+        match fs::remove_dir_all(&path) {
+            Ok(()) => info!(path = %path.display(), "Stale database generation removed"),
+            Err(err) => {
+                warn!(path = %path.display(), "Failed to remove stale database generation: {err:#}")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Turns a plain `db_path` directory into a generation which `db_path` links to, so that
+/// later swaps only replace the link. Must be called before the database is opened.
+pub fn link_plain_directory(db_path: &Path) -> Result<()> {
+    let is_dir = fs::symlink_metadata(db_path)
+        .map(|metadata| metadata.is_dir())
+        .unwrap_or(false);
+    if !is_dir {
+        return Ok(());
+    }
+    let generation = new_generation(db_path);
+    fs::rename(db_path, &generation)
+        .with_context(|| format!("Failed to move {} aside", db_path.display()))?;
+    point_to_generation(db_path, &generation)?;
+    info!(path = %generation.display(), "Database moved to a generation");
+    Ok(())
+}
+
+/// Makes `db_path`, which must not be a plain directory, a symlink to `generation`.
+pub fn point_to_generation(db_path: &Path, generation: &Path) -> Result<()> {
+    let next_link = with_suffix(db_path, ".next");
+    let _ = fs::remove_file(&next_link);
+    symlink(generation.file_name().context("Bad DB path")?, &next_link)?;
+    fs::rename(&next_link, db_path)
+        .with_context(|| format!("Failed to point {} to the new DB", db_path.display()))?;
+    Ok(())
+}
+
+/// Replays every archive into a fresh database next to `db_path`, then makes it the one served
+/// over HTTP and opened on the next start. A rebuild interrupted by a restart resumes where it
+/// stopped. The previous database is removed by `remove_stale_generations` on the next start.
+pub async fn rebuild(
+    db: &SwappableDatabase,
+    db_path: &Path,
+    options: &IngestOptions,
+) -> Result<Database> {
+    let rebuilding = rebuilding_link(db_path);
+    let generation = match link_target(&rebuilding) {
+        Some(generation) if generation.is_dir() => {
+            info!(path = %generation.display(), "Resuming database rebuild");
+            generation
+        }
+        _ => {
+            let generation = new_generation(db_path);
+            let _ = fs::remove_file(&rebuilding);
+            symlink(generation.file_name().context("Bad DB path")?, &rebuilding)?;
+            info!(path = %generation.display(), "Rebuilding database");
+            generation
+        }
+    };
+    let new_db = open_database(&generation)?;
+    // Backups of the half-built database would replace those of the served one.
+    let options = IngestOptions {
+        backups: None,
+        ..options.clone()
+    };
+    process_new_archives(&new_db, &options).await?;
+    db.swap(new_db.clone());
+    let previous = link_target(db_path);
+    point_to_generation(db_path, &generation)?;
+    fs::remove_file(&rebuilding)?;
+    info!(path = %generation.display(), "Rebuilt database swapped in");
+    if let Some(previous) = previous {
+        // Handlers may still be reading it, so it is only removed on the next start.
+        info!(path = %previous.display(), "Previous database left for removal on the next start");
+    }
+    Ok(new_db)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::symlink};
+
+    use super::{link_plain_directory, remove_stale_generations, with_suffix};
+
+    #[test]
+    fn removes_only_stale_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db");
+        for name in [
+            "db.1",
+            "db.2",
+            "db.3",
+            "db.original",
+            "db.backup",
+            "other.4",
+        ] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        symlink("db.2", &db_path).unwrap();
+        symlink("db.3", with_suffix(&db_path, ".rebuilding")).unwrap();
+        remove_stale_generations(&db_path).unwrap();
+        let mut names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "db",
+                "db.2",
+                "db.3",
+                "db.backup",
+                "db.original",
+                "db.rebuilding",
+                "other.4"
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_directory_becomes_linked_generation() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db");
+        fs::create_dir(&db_path).unwrap();
+        fs::write(db_path.join("CURRENT"), "MANIFEST-000001").unwrap();
+        link_plain_directory(&db_path).unwrap();
+        let target = fs::read_link(&db_path).unwrap();
+        assert!(target.to_str().unwrap().starts_with("db."), "{target:?}");
+        assert_eq!(
+            fs::read_to_string(db_path.join("CURRENT")).unwrap(),
+            "MANIFEST-000001"
+        );
+        // Already a link, nothing to do.
+        link_plain_directory(&db_path).unwrap();
+        assert_eq!(fs::read_link(&db_path).unwrap(), target);
+    }
+}
@@ -0,0 +1,35 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{Context, Result};
+use rkyvdb::Database;
+use tokio::{task::spawn_blocking, time::sleep};
+use tracing::info;
+
+use super::{admin, database_builder, http, rebuild::SwappableDatabase};
+
+const CATCH_UP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Serves queries from a secondary instance of the database at `db_path`, which another
+/// process keeps ingesting into. A rebuild by the primary is only picked up on restart, as it
+/// moves `db_path` to a new directory.
+pub async fn serve(db_path: &Path, secondary_path: &Path, http_port: u16) -> Result<()> {
+    let db = database_builder().open_secondary(
+        db_path.to_str().context("Non UTF-8 DB path")?,
+        secondary_path.to_str().context("Non UTF-8 replica path")?,
+    )?;
+    info!(path = %secondary_path.display(), "Serving a replica of the database");
+    let served_db = SwappableDatabase::new(db.clone());
+    tokio::select! {
+      v = http::serve(&served_db, http_port) => v,
+      v = catch_up_task(db) => v,
+      v = admin::export_db_metrics_task(served_db.clone()) => v,
+    }
+}
+
+async fn catch_up_task(db: Database) -> Result<()> {
+    loop {
+        sleep(CATCH_UP_INTERVAL).await;
+        let db = db.clone();
+        spawn_blocking(move || db.catch_up_with_primary()).await??;
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use rkyvdb::{Collection, Database, Store};
+use tracing::info;
+
+use crate::{
+    data::{Statistics, User},
+    util::ROOTS,
+};
+
+/// Changes of per-number user counts, indexed like `ROOTS`.
+pub type StatisticsDelta = Vec<BTreeMap<u32, i64>>;
+
+pub fn new_delta() -> StatisticsDelta {
+    vec![BTreeMap::new(); ROOTS.len()]
+}
+
+pub fn apply_delta(delta: &StatisticsDelta, db: &impl Store) -> Result<()> {
+    if delta.iter().all(BTreeMap::is_empty) {
+        return Ok(());
+    }
+    Statistics::modify((), db, |statistics| {
+        let mut statistics = statistics.unwrap_or_default();
+        for (root, root_delta) in ROOTS.iter().zip(delta) {
+            let current = &mut statistics
+                .roots
+                .entry(root.name.to_string())
+                .or_default()
+                .current;
+            for (erdos_number, change) in root_delta {
+                let count = current.entry(*erdos_number).or_default();
+                *count = count.saturating_add_signed(*change);
+            }
+            current.retain(|_, count| *count > 0);
+        }
+        Some(statistics)
+    })?;
+    Ok(())
+}
+
+/// Records current counts as the state after the archive of the given month.
+pub fn snapshot(month: &str, db: &impl Store) -> Result<()> {
+    Statistics::modify((), db, |statistics| {
+        let mut statistics = statistics.unwrap_or_default();
+        for root_statistics in statistics.roots.values_mut() {
+            root_statistics
+                .months
+                .insert(month.to_string(), root_statistics.current.clone());
+        }
+        Some(statistics)
+    })?;
+    Ok(())
+}
+
+/// Replaces current counts with ones computed from all users. Needed once for databases whose
+/// users were ingested before counts were kept up to date. Snapshots of earlier months can't
+/// be recovered and stay missing.
+pub fn build(db: &Database) -> Result<()> {
+    info!("Building statistics from users");
+    let mut counts = vec![BTreeMap::<u32, u64>::new(); ROOTS.len()];
+    for entry in User::iter(db)? {
+        let (_, user) = entry?;
+        for (root_counts, root) in counts.iter_mut().zip(ROOTS) {
+            let erdos_link = user
+                .erdos_links
+                .get(root.name)
+                .and_then(|erdos_links| erdos_links.last());
+            if let Some(erdos_link) = erdos_link {
+                *root_counts.entry(erdos_link.erdos_number).or_default() += 1;
+            }
+        }
+    }
+    Statistics::modify((), db, |statistics| {
+        let mut statistics = statistics.unwrap_or_default();
+        for (root, current) in ROOTS.iter().zip(counts) {
+            statistics
+                .roots
+                .entry(root.name.to_string())
+                .or_default()
+                .current = current;
+        }
+        Some(statistics)
+    })?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rkyvdb::{CaseInsensitiveString, Collection, Database};
+
+use crate::data::Win;
+
+/// Upper bound on players whose wins are read for a single query.
+const MAX_EXPANDED: usize = 100_000;
+
+struct Label {
+    id: String,
+    win: Option<Win>,
+    parent: Option<usize>,
+}
+
+/// Finds a path with the fewest wins from `from` to `to` where every win was played before
+/// the previous one, i.e. each loser had already (indirectly) beaten `to` when they lost.
+///
+/// Breadth-first search over the wins graph. A player is expanded again on a later level only
+/// if they're reached through a later win, since that leaves more of their wins usable.
+#[tracing::instrument(skip(db))]
+pub fn find_winning_path(from: &str, to: &str, db: &Database) -> Result<Option<Vec<Win>>> {
+    let mut labels = vec![Label {
+        id: from.to_string(),
+        win: None,
+        parent: None,
+    }];
+    let mut latest_win: HashMap<String, DateTime<Utc>> = HashMap::new();
+    let mut frontier = vec![0];
+    let mut expanded = 0;
+    while !frontier.is_empty() {
+        let mut next_frontier = vec![];
+        for label_index in frontier {
+            expanded += 1;
+            if expanded > MAX_EXPANDED {
+                return Ok(None);
+            }
+            let before = labels[label_index].win.as_ref().map(|win| win.time);
+            let winner = CaseInsensitiveString::from(labels[label_index].id.as_str());
+            // The latest win over each loser before the previous hop, which leaves the most
+            // wins of the loser usable. Wins are ordered by loser, then by time.
+            let mut latest_wins: Vec<Win> = vec![];
+            for entry in Win::prefix_iter_by(&(winner,), db)? {
+                let (_, win) = entry?;
+                if before.map_or(false, |before| win.time >= before) {
+                    continue;
+                }
+                match latest_wins.last_mut() {
+                    Some(latest) if latest.loser_id.eq_ignore_ascii_case(&win.loser_id) => {
+                        *latest = win;
+                    }
+                    _ => latest_wins.push(win),
+                }
+            }
+            for win in latest_wins {
+                if win.loser_id.eq_ignore_ascii_case(to) {
+                    let mut path = vec![win];
+                    let mut index = Some(label_index);
+                    while let Some(label) = index.map(|index| &labels[index]) {
+                        path.extend(label.win.clone());
+                        index = label.parent;
+                    }
+                    path.reverse();
+                    return Ok(Some(path));
+                }
+                let key = win.loser_id.to_lowercase();
+                if key == from.to_lowercase()
+                    || latest_win.get(&key).map_or(false, |time| *time >= win.time)
+                {
+                    continue;
+                }
+                latest_win.insert(key, win.time);
+                next_frontier.push(labels.len());
+                labels.push(Label {
+                    id: win.loser_id.clone(),
+                    win: Some(win),
+                    parent: Some(label_index),
+                });
+            }
+        }
+        frontier = next_frontier;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rkyvdb::{Collection, Database};
+
+    use super::find_winning_path;
+    use crate::data::Win;
+
+    fn open() -> Database {
+        crate::server::database_builder().open_in_memory().unwrap()
+    }
+
+    fn record_win(db: &Database, winner: &str, loser: &str, hour: u32) {
+        let win = Win {
+            loser_id: loser.to_string(),
+            time: Utc.with_ymd_and_hms(2014, 1, 1, hour, 0, 0).unwrap(),
+            game_id: format!("{winner}-{loser}-{hour}"),
+        };
+        Win::put(win.key(winner), &win, db).unwrap();
+    }
+
+    fn games(db: &Database, from: &str, to: &str) -> Option<Vec<String>> {
+        find_winning_path(from, to, db)
+            .unwrap()
+            .map(|path| path.into_iter().map(|win| win.game_id).collect())
+    }
+
+    #[test]
+    fn uses_earlier_win_than_latest() {
+        let db = open();
+        record_win(&db, "alice", "bob", 5);
+        record_win(&db, "bob", "carol", 3);
+        record_win(&db, "bob", "carol", 7);
+        assert_eq!(
+            games(&db, "alice", "carol"),
+            Some(vec!["alice-bob-5".to_string(), "bob-carol-3".to_string()])
+        );
+        // Wins must be played before the previous one.
+        assert_eq!(games(&db, "alice", "dave"), None);
+        record_win(&db, "carol", "dave", 4);
+        assert_eq!(games(&db, "alice", "dave"), None);
+    }
+
+    #[test]
+    fn expands_player_again_through_later_win() {
+        let db = open();
+        record_win(&db, "alice", "carol", 2);
+        record_win(&db, "alice", "bob", 10);
+        record_win(&db, "bob", "carol", 9);
+        record_win(&db, "carol", "dave", 5);
+        // carol is first reached through the win at 2, before her win over dave.
+        assert_eq!(
+            games(&db, "Alice", "DAVE"),
+            Some(vec![
+                "alice-bob-10".to_string(),
+                "bob-carol-9".to_string(),
+                "carol-dave-5".to_string(),
+            ])
+        );
+    }
+}
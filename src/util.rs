@@ -1 +1,63 @@
-pub const ERDOS_ID: &str = "DrNykterstein";
+use chrono::{DateTime, TimeZone, Utc};
+
+/// A player whose number is 0. Numbers are computed for every root independently.
+#[derive(Debug, PartialEq)]
+pub struct Root {
+    /// Short name used in URLs.
+    pub name: &'static str,
+    /// Lichess id of the player.
+    pub id: &'static str,
+    /// UTC date (year, month, day) since which games count towards this root. Changing it
+    /// requires a `--rebuild`, as links of games before the new date would be kept.
+    pub start: (i32, u32, u32),
+    /// Title and link of the event which made the player a root.
+    pub event: (&'static str, &'static str),
+    /// UTC date (year, month, day) of the event, shown as the start of the root's reign.
+    pub event_date: (i32, u32, u32),
+}
+
+impl Root {
+    pub fn find(name: &str) -> Option<&'static Root> {
+        ROOTS.iter().find(|root| root.name == name)
+    }
+
+    pub fn start(&self) -> DateTime<Utc> {
+        to_datetime(self.start)
+    }
+
+    pub fn event_date(&self) -> DateTime<Utc> {
+        to_datetime(self.event_date)
+    }
+}
+
+fn to_datetime((year, month, day): (i32, u32, u32)) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+}
+
+/// Adding a root requires a `--rebuild`, as archives processed before it weren't checked for
+/// its links.
+pub const ROOTS: &[Root] = &[
+    Root {
+        name: "magnus",
+        id: "DrNykterstein",
+        // Every game counts, as before roots had a start date.
+        start: (2013, 1, 1),
+        event: (
+            "WCC 2013",
+            "https://en.wikipedia.org/wiki/World_Chess_Championship_2013",
+        ),
+        event_date: (2013, 11, 22),
+    },
+    Root {
+        name: "ding",
+        id: "DingLiren",
+        start: (2023, 4, 30),
+        event: (
+            "WCC 2023",
+            "https://en.wikipedia.org/wiki/World_Chess_Championship_2023",
+        ),
+        event_date: (2023, 4, 30),
+    },
+];
+
+pub const DEFAULT_ROOT: &Root = &ROOTS[0];